@@ -35,7 +35,7 @@ fn main() {
         Ok(indicators) => {
             //println!("indicators: {:?}", indicators);
             for i in indicators.iter() {
-                println!("{:?}", i);
+                println!("{} ({}): {}", i.id(), i.pattern_type(), i.pattern());
             }
             println!("{:?}", indicators.len());
         }
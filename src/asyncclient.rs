@@ -0,0 +1,530 @@
+use crate::{
+    build_objects_url,
+    cctaxiiclient::{
+        basic_auth, default_collection, normalize_base_url, validate_added_after,
+        validate_version_matches, CCEnvelope,
+    },
+    metrics::NoMetrics,
+    taxiiclient::{Collections, Discovery, ServerOverview},
+    transport::status_error,
+    CCIndicator, Metrics, Result, TaxiiError,
+    TaxiiError::{
+        JsonDeserializationError, TaxiiAuthorizationError, TaxiiConnectionError, Timeout,
+    },
+};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
+use std::future::{poll_fn, Future};
+use std::pin::pin;
+use std::sync::RwLock;
+use std::task::Poll;
+use std::time::Duration;
+
+/// An asynchronous client for the `CloudCover` TAXII server, backed by `reqwest`. Available
+/// with the `async` feature.
+///
+/// It mirrors the read API of `CCTaxiiClient` for use inside async services, such as a
+/// tokio-based ingestion pipeline, without spawning a blocking task per pull. Errors are the
+/// same `TaxiiError` variants the synchronous client returns, and the user agent, metrics sink,
+/// and token refresh callback are honored as they are there.
+///
+/// # Fields
+///
+/// - `client`: The `reqwest` client used to send requests.
+/// - `base_url`: The base URL of the TAXII server.
+/// - `account`: Username/account name, which is also the private API root.
+/// - `authorization`: The `Authorization` header sent with every request.
+/// - `timeout`: How long a single request may take.
+/// - `user_agent`: The `User-Agent` header sent with every request, if set.
+/// - `metrics`: The sink receiving request, error, and byte counts.
+/// - `token_refresh`: Callback obtaining a fresh bearer token on a 401 during pagination.
+/// - `refreshed_authorization`: Authorization header built from the last refreshed token.
+///
+/// # Examples
+///
+/// ```
+/// let agent = AsyncCCTaxiiClient::new("my_username", "my_api_key");
+/// let indicators = agent
+///     .get_cc_indicators(None, Some(100), false, None, &None, true)
+///     .await?;
+/// ```
+pub struct AsyncCCTaxiiClient {
+    client: reqwest::Client,
+    base_url: String,
+    account: String,
+    authorization: String,
+    timeout: Duration,
+    user_agent: Option<String>,
+    metrics: Box<dyn Metrics>,
+    token_refresh: Option<Box<dyn Fn() -> String + Send + Sync>>,
+    refreshed_authorization: RwLock<Option<String>>,
+}
+
+impl AsyncCCTaxiiClient {
+    /// Creates a new asynchronous client authenticating with the given credentials.
+    ///
+    /// # Parameters
+    ///
+    /// - `username`: The username for TAXII server authentication.
+    /// - `api_key`: The API key or password for TAXII server authentication.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = AsyncCCTaxiiClient::new("my_username", "my_api_key");
+    /// ```
+    #[must_use]
+    pub fn new(username: &str, api_key: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: "https://taxii2.cloudcover.net".to_string(),
+            account: username.to_string(),
+            authorization: basic_auth(username, api_key),
+            timeout: Duration::from_secs(30),
+            user_agent: None,
+            metrics: Box::new(NoMetrics),
+            token_refresh: None,
+            refreshed_authorization: RwLock::new(None),
+        }
+    }
+
+    /// Points the client at another TAXII server, as `CCTaxiiClient::with_base_url` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidBaseUrl` if the URL is not a valid `http` or `https` URL.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Result<Self> {
+        self.base_url = normalize_base_url(&base_url.into())?;
+        Ok(self)
+    }
+
+    /// Sets how long a single request may take before it fails with `Timeout`, as
+    /// `CCTaxiiClient::with_timeout` does. The default is 30 seconds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = AsyncCCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_timeout(Duration::from_secs(10));
+    /// ```
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Sets the `User-Agent` header sent with every request, as
+    /// `CCTaxiiClientBuilder::user_agent` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = AsyncCCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_user_agent("acme-soar/1.0");
+    /// ```
+    #[must_use]
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Installs a sink receiving metrics about the client's requests, as
+    /// `CCTaxiiClient::with_metrics` does.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = AsyncCCTaxiiClient::new("my_username", "my_api_key").with_metrics(my_sink);
+    /// ```
+    #[must_use]
+    pub fn with_metrics<M: Metrics + 'static>(mut self, metrics: M) -> Self {
+        self.metrics = Box::new(metrics);
+        self
+    }
+
+    /// Sets a callback obtaining a fresh bearer token when a page request is rejected with
+    /// 401, as `CCTaxiiClient::with_token_refresh` does.
+    ///
+    /// The `Authorization` header becomes `Bearer <token>` for all later requests, and the
+    /// page is retried once.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = AsyncCCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_token_refresh(|| fetch_new_token());
+    /// ```
+    #[must_use]
+    pub fn with_token_refresh<F>(mut self, refresh: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        self.token_refresh = Some(Box::new(refresh));
+        self
+    }
+
+    /// Sends a GET request for `url`, relative to the base URL, and parses the JSON response,
+    /// reporting the request, its errors, and the bytes read to the metrics sink.
+    async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        self.metrics.on_request("GET", url);
+        let result = self.send_get(url).await;
+        if let Err(e) = &result {
+            self.metrics.on_error(e);
+        }
+        result
+    }
+
+    /// Sends a GET request for `url` and parses the JSON response.
+    async fn send_get<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        let authorization = self
+            .refreshed_authorization
+            .read()
+            .ok()
+            .and_then(|auth| auth.clone())
+            .unwrap_or_else(|| self.authorization.clone());
+        let mut request = self
+            .client
+            .get(format!("{}/{url}", self.base_url))
+            .header("Accept", "application/taxii+json;version=2.1")
+            .header("Content-Type", "application/taxii+json;version=2.1")
+            .header("Authorization", authorization)
+            .timeout(self.timeout);
+        if let Some(user_agent) = &self.user_agent {
+            request = request.header("User-Agent", user_agent);
+        }
+        let response = request.send().await.map_err(|e| request_error(&e))?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| request_error(&e))?;
+        self.metrics.on_bytes(body.len() as u64);
+        if !status.is_success() {
+            let reason = status.canonical_reason().unwrap_or_default();
+            let response = ureq::Response::new(status.as_u16(), reason, &body)
+                .map_err(|e| TaxiiConnectionError(e.to_string()))?;
+            return Err(status_error(status.as_u16(), response));
+        }
+        serde_json::from_str(&body).map_err(|e| Box::new(JsonDeserializationError(e.to_string())))
+    }
+
+    /// Requests a page, refreshing the bearer token and retrying once on a 401 if a
+    /// `token_refresh` callback is set.
+    async fn get_page<T: DeserializeOwned>(&self, url: &str) -> Result<T> {
+        match (self.get_json(url).await, &self.token_refresh) {
+            (Err(e), Some(refresh)) if matches!(*e, TaxiiAuthorizationError(_)) => {
+                let auth = format!("Bearer {}", refresh());
+                if let Ok(mut refreshed) = self.refreshed_authorization.write() {
+                    *refreshed = Some(auth);
+                }
+                self.get_json(url).await
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Retrieves discovery information from the TAXII server.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `CCTaxiiClient::get_discovery`.
+    pub async fn get_discovery(&self) -> Result<Discovery> {
+        self.get_json("taxii2/").await
+    }
+
+    /// Retrieves the collection IDs of an API root, `api` if `root` is `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `CCTaxiiClient::get_collections`.
+    pub async fn get_collections(&self, root: Option<&str>) -> Result<Vec<String>> {
+        let url = format!("{}/collections/", root.unwrap_or("api"));
+        let collections: Collections = self.get_json(&url).await?;
+        Ok(collections.collections.into_iter().map(|c| c.id).collect())
+    }
+
+    /// Retrieves the discovery information and the detailed collections of the public or
+    /// private API root together, as `CCTaxiiClient::overview` does.
+    ///
+    /// Both requests are in flight at the same time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = AsyncCCTaxiiClient::new("my_username", "my_api_key");
+    /// let overview = agent.overview(false).await?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `CCTaxiiClient::overview`.
+    pub async fn overview(&self, private: bool) -> Result<ServerOverview> {
+        let root = if private {
+            self.account.as_str()
+        } else {
+            "api"
+        };
+        let url = format!("{root}/collections/");
+        let mut discovery_request = pin!(self.get_discovery());
+        let mut collections_request = pin!(self.get_json::<Collections>(&url));
+        let (mut discovery, mut collections) = (None, None);
+        let (discovery, collections) = poll_fn(|cx| {
+            if discovery.is_none() {
+                if let Poll::Ready(result) = discovery_request.as_mut().poll(cx) {
+                    discovery = Some(result);
+                }
+            }
+            if collections.is_none() {
+                if let Poll::Ready(result) = collections_request.as_mut().poll(cx) {
+                    collections = Some(result);
+                }
+            }
+            match (discovery.take(), collections.take()) {
+                (Some(d), Some(c)) => Poll::Ready((d, c)),
+                (d, c) => {
+                    (discovery, collections) = (d, c);
+                    Poll::Pending
+                }
+            }
+        })
+        .await;
+        Ok(ServerOverview {
+            discovery: discovery?,
+            collections: collections?.collections,
+        })
+    }
+
+    /// Retrieves cyber threat indicators from a collection, awaiting each page in turn.
+    ///
+    /// The parameters and defaults are those of `CCTaxiiClient::get_cc_indicators`: the
+    /// [default collection](crate::CCTaxiiClient#default-collection) is chosen the same way,
+    /// and the page size defaults to 1000.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = AsyncCCTaxiiClient::new("my_username", "my_api_key");
+    /// let indicators = agent
+    ///     .get_cc_indicators(None, None, false, None, &None, true)
+    ///     .await?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `CCTaxiiClient::get_cc_indicators`.
+    #[allow(clippy::ref_option)]
+    pub async fn get_cc_indicators(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+        follow_pages: bool,
+    ) -> Result<Vec<CCIndicator>> {
+        validate_added_after(added_after)?;
+        validate_version_matches(matches.as_ref())?;
+        let root = if private {
+            self.account.as_str()
+        } else {
+            "api"
+        };
+        let collection = if let Some(id) = collection_id {
+            id.to_string()
+        } else {
+            let url = format!("{root}/collections/");
+            default_collection(self.get_json::<Collections>(&url).await?.collections)?
+        };
+        let limit = limit.unwrap_or(1000);
+        let mut indicators: Vec<CCIndicator> = Vec::new();
+        let mut next: Option<String> = None;
+        loop {
+            let url = build_objects_url(
+                root,
+                &collection,
+                limit,
+                added_after,
+                matches,
+                next.as_deref(),
+            );
+            let page: CCEnvelope = self.get_page(&url).await?;
+            let more = follow_pages && page.more();
+            next = page.next().map(str::to_string);
+            indicators.extend(page);
+            if !more || next.is_none() {
+                return Ok(indicators);
+            }
+        }
+    }
+}
+
+/// Builds the error for a request that failed to complete, distinguishing timeouts and
+/// keeping the cause.
+fn request_error(error: &reqwest::Error) -> Box<TaxiiError> {
+    if error.is_timeout() {
+        Box::new(Timeout(error.to_string()))
+    } else {
+        Box::new(TaxiiConnectionError(error.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+
+    // The server must outlive every request made by the agent.
+    #[allow(clippy::significant_drop_tightening)]
+    #[tokio::test]
+    async fn async_indicators_test() {
+        let mut server = mockito::Server::new_async().await;
+        let indicator = |id: &str| {
+            fixtures::indicator_value(id, "2024-01-01T00:00:00Z", fixtures::PATTERN).to_string()
+        };
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(r#"{"collections":[{"can_read":true,"can_write":false,"id":"c2","media_types":["application/stix+json;version=2.1"],"name":"b","title":"B"},{"can_read":true,"can_write":false,"id":"c1","media_types":["application/stix+json;version=2.1"],"name":"a","title":"A"}]}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1".into()))
+            .with_body(format!(
+                r#"{{"more":true,"next":"p2","objects":[{}]}}"#,
+                indicator("indicator--1")
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1&next=p2".into()))
+            .with_body(format!(
+                r#"{{"more":false,"objects":[{}]}}"#,
+                indicator("indicator--2")
+            ))
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/collections/c9/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .create_async()
+            .await;
+        let agent = AsyncCCTaxiiClient::new("user", "key")
+            .with_base_url(server.url())
+            .expect("Invalid mock URL");
+        let indicators = agent
+            .get_cc_indicators(None, Some(1), false, None, &None, true)
+            .await
+            .expect("Failed to get indicators");
+        let ids: Vec<&str> = indicators.iter().map(CCIndicator::id).collect();
+        assert_eq!(ids, ["indicator--1", "indicator--2"]);
+        let missing = agent
+            .get_cc_indicators(Some("c9"), None, false, None, &None, true)
+            .await;
+        assert!(matches!(
+            missing.map_err(|e| *e),
+            Err(crate::TaxiiError::TaxiiNotFound(_))
+        ));
+    }
+
+    // The server must outlive every request made by the agent.
+    #[allow(clippy::significant_drop_tightening)]
+    #[tokio::test]
+    async fn async_overview_test() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/taxii2/")
+            .with_body(r#"{"api_roots":["/api/"],"contact":"it.support@cloudcover.net","default":"/api/","description":"Mock TAXII server","title":"CloudCover TAXII Server"}"#)
+            .create_async()
+            .await;
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(r#"{"collections":[{"can_read":true,"can_write":false,"id":"c1","media_types":["application/stix+json;version=2.1"],"name":"a","title":"A"}]}"#)
+            .create_async()
+            .await;
+        let agent = AsyncCCTaxiiClient::new("user", "key")
+            .with_base_url(server.url())
+            .expect("Invalid mock URL");
+        let overview = agent.overview(false).await.expect("Failed to get overview");
+        assert_eq!(overview.discovery.title, "CloudCover TAXII Server");
+        let ids: Vec<&str> = overview.collections.iter().map(|c| c.id.as_str()).collect();
+        assert_eq!(ids, ["c1"]);
+    }
+
+    // The server must outlive every request made by the agent.
+    #[allow(clippy::significant_drop_tightening)]
+    #[tokio::test]
+    async fn async_client_options_test() {
+        struct Requests(std::sync::atomic::AtomicUsize);
+
+        impl Metrics for std::sync::Arc<Requests> {
+            fn on_request(&self, _method: &str, _url: &str) {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        let mut server = mockito::Server::new_async().await;
+        let rejected = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .match_header("Authorization", "Basic dXNlcjprZXk=")
+            .with_status(401)
+            .expect(1)
+            .create_async()
+            .await;
+        let refreshed = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .match_header("Authorization", "Bearer fresh")
+            .match_header("User-Agent", "acme-soar/1.0")
+            .with_body(r#"{"more":false}"#)
+            .expect(1)
+            .create_async()
+            .await;
+        let requests = std::sync::Arc::new(Requests(std::sync::atomic::AtomicUsize::new(0)));
+        let agent = AsyncCCTaxiiClient::new("user", "key")
+            .with_base_url(server.url())
+            .expect("Invalid mock URL")
+            .with_user_agent("acme-soar/1.0")
+            .with_metrics(std::sync::Arc::clone(&requests))
+            .with_token_refresh(|| "fresh".to_string());
+        let indicators = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, true)
+            .await
+            .expect("Failed to get indicators after refresh");
+        assert!(indicators.is_empty());
+        assert_eq!(requests.0.load(std::sync::atomic::Ordering::SeqCst), 2);
+        rejected.assert_async().await;
+        refreshed.assert_async().await;
+    }
+
+    // The server must outlive every request made by the agent.
+    #[allow(clippy::significant_drop_tightening)]
+    #[tokio::test]
+    async fn async_request_error_test() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/taxii2/")
+            .with_chunked_body(|body| {
+                std::thread::sleep(Duration::from_millis(500));
+                body.write_all(b"{}")
+            })
+            .create_async()
+            .await;
+        let agent = AsyncCCTaxiiClient::new("user", "key")
+            .with_base_url(server.url())
+            .expect("Invalid mock URL")
+            .with_timeout(Duration::from_millis(100));
+        let result = agent.get_discovery().await.map_err(|e| *e);
+        assert!(matches!(result, Err(Timeout(_))), "{result:?}");
+
+        let agent = AsyncCCTaxiiClient::new("user", "key")
+            .with_base_url("http://127.0.0.1:1")
+            .expect("Invalid URL");
+        let Err(error) = agent.get_discovery().await.map_err(|e| *e) else {
+            panic!("Connection to a closed port succeeded");
+        };
+        let TaxiiConnectionError(message) = error else {
+            panic!("Unexpected error: {error}");
+        };
+        assert!(message.contains("127.0.0.1:1"), "{message}");
+    }
+}
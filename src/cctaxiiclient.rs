@@ -1,16 +1,75 @@
 use crate::{
-    taxiiclient::Collections,
+    metrics::{Metrics, NoMetrics},
+    pattern::parse_comparison,
+    taxiiclient::ApiRoot,
     taxiiclient::Discovery,
-    Result, TaxiiClient,
+    taxiiclient::{Collection, Collections},
+    taxiiclient::{Envelope, Manifest, ManifestRecord, ServerOverview, Status},
+    transport::Transport,
+    Result, TaxiiClient, TaxiiError,
     TaxiiError::{
+        Cancelled, IntegrityError, InvalidBaseUrl, InvalidFilter, IoError,
         JsonDeserializationError, TaxiiAuthorizationError, TaxiiCollectionError,
-        TaxiiConnectionError, TaxiiGenericError, TaxiiNotFound,
+        TaxiiConnectionError, Timeout, UnexpectedContentType,
     },
 };
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::time::Duration;
-use ureq::{Agent, Response};
+#[cfg(feature = "chrono")]
+use chrono::{DateTime, SecondsFormat, Utc};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Read, Write};
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::atomic::{self, AtomicBool};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+use ureq::Response;
+
+/// Callback returning a fresh bearer token after the current one expired.
+type TokenRefresh = Box<dyn Fn() -> String + Send + Sync>;
+
+/// The base URL of the `CloudCover` TAXII server, used by `new`.
+const DEFAULT_BASE_URL: &str = "https://taxii2.cloudcover.net";
+
+/// The number of `Warning` header values kept by a client, the most recent ones.
+const MAX_WARNINGS: usize = 100;
+
+/// The default maximum length of a request URL built from a list of ids, in bytes.
+const DEFAULT_MAX_URL_LENGTH: usize = 4096;
+
+/// The media type of the STIX objects the client reads and writes.
+const STIX_MEDIA_TYPE: &str = "application/stix+json;version=2.1";
+
+/// Placeholder for the API root in endpoint path templates.
+const ROOT_PLACEHOLDER: &str = "{root}";
+
+/// Placeholder for the collection ID in endpoint path templates.
+const COLLECTION_PLACEHOLDER: &str = "{collection}";
+
+/// Characters percent-encoded in a single `match[...]` value: all but the RFC 3986 unreserved
+/// characters, so STIX types such as `attack-pattern` are sent as they are.
+const MATCH_VALUE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// The API root and collection an indicator was retrieved from.
+///
+/// # Fields
+///
+/// - `root`: The API root (e.g., `api`, or the account name for the private root).
+/// - `collection_id`: The ID of the collection within the root.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SourceInfo {
+    pub root: String,
+    pub collection_id: String,
+}
 
 /// Represents an Indicator of Compromise (`IoC`) within a TAXII feed.
 ///
@@ -18,9 +77,11 @@ use ureq::{Agent, Response};
 ///
 /// # Fields
 ///
+/// - `confidence`: The producer's confidence in the `IoC`, from 0 to 100, if provided.
 /// - `created`: The creation date of the `IoC`.
-/// - `description`: A human-readable description of the `IoC`.
+/// - `description`: A human-readable description of the `IoC`, if provided.
 /// - `id`: The unique identifier of the `IoC`.
+/// - `labels`: The STIX `labels` of the `IoC` (e.g., `malicious-activity`), empty if none.
 /// - `modified`: The last modification date of the `IoC`.
 /// - `name`: The name of the `IoC`.
 /// - `pattern`: The pattern of the `IoC` used for matching.
@@ -29,11 +90,22 @@ use ureq::{Agent, Response};
 /// - `spec_version`: The TAXII specification version.
 /// - `type`: The type of the `IoC` (e.g., "indicator").
 /// - `valid_from`: The date from which the `IoC` is considered valid.
-#[derive(Deserialize, Debug)]
+///
+/// Absent optional fields are omitted, rather than written as `null`, when serializing.
+#[derive(Serialize, Deserialize, Debug)]
 pub struct CCIndicator {
+    #[serde(
+        default,
+        deserialize_with = "deserialize_confidence",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub confidence: Option<u8>,
     pub created: String,
-    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     pub id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
     pub modified: String,
     pub name: String,
     pub pattern: String,
@@ -44,21 +116,545 @@ pub struct CCIndicator {
     pub valid_from: String,
 }
 
-/// Represents a `CloudCover `TAXII Envelope, used for wrapping `CloudCover `TAXII objects.
+impl CCIndicator {
+    /// Returns the STIX `confidence` score of the indicator, from 0 to 100, if provided.
+    #[must_use]
+    pub const fn confidence(&self) -> Option<u8> {
+        self.confidence
+    }
+
+    /// Returns the STIX identifier of the indicator (e.g., `indicator--<uuid>`).
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the STIX `labels` of the indicator, empty if it has none.
+    #[must_use]
+    pub fn labels(&self) -> &[String] {
+        &self.labels
+    }
+
+    /// Returns the STIX type of the object, `indicator` for indicators.
+    #[must_use]
+    pub fn r#type(&self) -> &str {
+        &self.r#type
+    }
+
+    /// Returns the name of the indicator.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the detection pattern of the indicator.
+    #[must_use]
+    pub fn pattern(&self) -> &str {
+        &self.pattern
+    }
+
+    /// Returns the language of the pattern (e.g., `stix`, `sigma`, `yara`).
+    #[must_use]
+    pub fn pattern_type(&self) -> &str {
+        &self.pattern_type
+    }
+
+    /// Returns the version of the pattern language.
+    #[must_use]
+    pub fn pattern_version(&self) -> &str {
+        &self.pattern_version
+    }
+
+    /// Returns the STIX specification version of the object.
+    #[must_use]
+    pub fn spec_version(&self) -> &str {
+        &self.spec_version
+    }
+
+    /// Returns the timestamp at which the indicator was created.
+    #[must_use]
+    pub fn created(&self) -> &str {
+        &self.created
+    }
+
+    /// Returns the timestamp at which this version of the indicator was last modified.
+    #[must_use]
+    pub fn modified(&self) -> &str {
+        &self.modified
+    }
+
+    /// Returns the timestamp from which the indicator is considered valid.
+    #[must_use]
+    pub fn valid_from(&self) -> &str {
+        &self.valid_from
+    }
+
+    /// Parses the `created` timestamp of the indicator.
+    ///
+    /// # Errors
+    ///
+    /// Returns `JsonDeserializationError` if `created` is not an RFC 3339 timestamp.
+    #[cfg(feature = "chrono")]
+    pub fn created_at(&self) -> Result<DateTime<Utc>> {
+        parse_timestamp("created", &self.created)
+    }
+
+    /// Parses the `modified` timestamp of the indicator.
+    ///
+    /// # Errors
+    ///
+    /// Returns `JsonDeserializationError` if `modified` is not an RFC 3339 timestamp.
+    #[cfg(feature = "chrono")]
+    pub fn modified_at(&self) -> Result<DateTime<Utc>> {
+        parse_timestamp("modified", &self.modified)
+    }
+
+    /// Parses the `valid_from` timestamp of the indicator.
+    ///
+    /// # Errors
+    ///
+    /// Returns `JsonDeserializationError` if `valid_from` is not an RFC 3339 timestamp.
+    #[cfg(feature = "chrono")]
+    pub fn valid_from_at(&self) -> Result<DateTime<Utc>> {
+        parse_timestamp("valid_from", &self.valid_from)
+    }
+
+    /// Returns the description of the indicator, if provided.
+    #[must_use]
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Returns the string fields of the indicator as a map keyed by their STIX property
+    /// names, for filling templated alerts or emails.
+    ///
+    /// `description` is only present when the indicator has one, and the numeric
+    /// `confidence` and the `labels` list are not included.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let fields = indicator.to_map();
+    /// let subject = format!("New {} indicator: {}", fields["pattern_type"], fields["name"]);
+    /// ```
+    #[must_use]
+    pub fn to_map(&self) -> HashMap<String, String> {
+        let mut map: HashMap<String, String> = [
+            ("created", &self.created),
+            ("id", &self.id),
+            ("modified", &self.modified),
+            ("name", &self.name),
+            ("pattern", &self.pattern),
+            ("pattern_type", &self.pattern_type),
+            ("pattern_version", &self.pattern_version),
+            ("spec_version", &self.spec_version),
+            ("type", &self.r#type),
+            ("valid_from", &self.valid_from),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect();
+        if let Some(description) = &self.description {
+            map.insert("description".to_string(), description.clone());
+        }
+        map
+    }
+}
+
+/// Parses the RFC 3339 timestamp held in the `field` property of an indicator.
+#[cfg(feature = "chrono")]
+fn parse_timestamp(field: &str, value: &str) -> Result<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|timestamp| timestamp.with_timezone(&Utc))
+        .map_err(|e| {
+            Box::new(JsonDeserializationError(format!(
+                "{field} is not an RFC 3339 timestamp: {value} ({e})"
+            )))
+        })
+}
+
+/// Formats a time as a TAXII timestamp, such as `2024-01-01T00:00:00.000Z`, for use as an
+/// `added_after` argument.
+///
+/// # Examples
+///
+/// ```
+/// let since = DateTime::<Utc>::from(SystemTime::now() - Duration::from_secs(86_400));
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+/// let indicators =
+///     agent.get_cc_indicators(None, None, false, Some(&taxii_timestamp(since)), &None, true)?;
+/// ```
+#[cfg(feature = "chrono")]
+#[must_use]
+pub fn taxii_timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Keeps only the indicators whose `valid_from` lies between `start` (inclusive) and `end`
+/// (exclusive).
+///
+/// `valid_from` is parsed as an RFC 3339 timestamp. Indicators whose `valid_from` cannot be
+/// parsed are dropped.
+///
+/// # Examples
+///
+/// ```
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+/// let indicators = agent.get_cc_indicators(None, None, false, None, &None, true)?;
+/// let end = Utc::now();
+/// let last_week = filter_valid_from_between(indicators, end - Duration::days(7), end);
+/// ```
+#[cfg(feature = "chrono")]
+#[must_use]
+pub fn filter_valid_from_between(
+    indicators: Vec<CCIndicator>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Vec<CCIndicator> {
+    indicators
+        .into_iter()
+        .filter(|indicator| {
+            DateTime::parse_from_rfc3339(&indicator.valid_from)
+                .is_ok_and(|valid_from| valid_from >= start && valid_from < end)
+        })
+        .collect()
+}
+
+/// Keeps only the indicators whose `confidence` is at least `min`.
+///
+/// Indicators without a `confidence` score are dropped.
+///
+/// # Examples
+///
+/// ```
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+/// let indicators = agent.get_cc_indicators(None, None, false, None, &None, true)?;
+/// let likely = filter_min_confidence(indicators, 75);
+/// ```
+#[must_use]
+pub fn filter_min_confidence(indicators: Vec<CCIndicator>, min: u8) -> Vec<CCIndicator> {
+    indicators
+        .into_iter()
+        .filter(|indicator| indicator.confidence.is_some_and(|c| c >= min))
+        .collect()
+}
+
+/// Returns the indicators whose `id` is not a valid STIX identifier.
+///
+/// A valid id has the form `<type>--<uuid>`, where the type is 3 to 250 lowercase ASCII
+/// letters, digits, and hyphens starting with a letter, and the UUID is in its hyphenated
+/// form (e.g., `indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f`).
+///
+/// # Examples
+///
+/// ```
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+/// let indicators = agent.get_cc_indicators(None, None, false, None, &None, true)?;
+/// for indicator in validate_ids(&indicators) {
+///     eprintln!("Malformed id: {}", indicator.id);
+/// }
+/// ```
+#[must_use]
+pub fn validate_ids(indicators: &[CCIndicator]) -> Vec<&CCIndicator> {
+    indicators
+        .iter()
+        .filter(|indicator| !is_stix_id(&indicator.id))
+        .collect()
+}
+
+/// Checks that `value` is a well-formed STIX bundle, such as one written by
+/// `dump_collection_bundle` and read back.
+///
+/// A well-formed bundle has `type` set to `"bundle"`, an `id` of the form `bundle--<uuid>`, and
+/// an optional `objects` array in which every entry is an object with a valid STIX `type`.
+///
+/// # Parameters
+///
+/// * `value` - The parsed bundle JSON.
+///
+/// # Errors
+///
+/// Returns `JsonDeserializationError` describing the first problem found.
+///
+/// # Examples
+///
+/// ```
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+/// agent.dump_collection_bundle(None, false, "bundle.json")?;
+/// let bundle = serde_json::from_reader(File::open("bundle.json")?)?;
+/// validate_bundle(&bundle)?;
+/// ```
+pub fn validate_bundle(value: &serde_json::Value) -> Result<()> {
+    let invalid = |reason: String| Box::new(JsonDeserializationError(reason));
+    if value["type"] != "bundle" {
+        return Err(invalid(format!("Bundle has wrong type: {}", value["type"])));
+    }
+    let id_ok = value["id"]
+        .as_str()
+        .is_some_and(|id| id.starts_with("bundle--") && is_stix_id(id));
+    if !id_ok {
+        return Err(invalid(format!("Bundle has malformed id: {}", value["id"])));
+    }
+    let objects = match value.get("objects") {
+        None => return Ok(()),
+        Some(serde_json::Value::Array(objects)) => objects,
+        Some(other) => return Err(invalid(format!("Bundle objects is not an array: {other}"))),
+    };
+    objects
+        .iter()
+        .find(|object| !object["type"].as_str().is_some_and(is_stix_type))
+        .map_or(Ok(()), |object| {
+            Err(invalid(format!("Object has no valid STIX type: {object}")))
+        })
+}
+
+/// Returns `true` if `id` has the STIX `<type>--<uuid>` form.
+fn is_stix_id(id: &str) -> bool {
+    let Some((object_type, uuid)) = id.split_once("--") else {
+        return false;
+    };
+    is_stix_type(object_type) && uuid.len() == 36 && uuid::Uuid::try_parse(uuid).is_ok()
+}
+
+/// Returns `true` if `object_type` is a valid STIX type name: 3 to 250 lowercase ASCII
+/// letters, digits, and hyphens, starting with a letter and not ending with a hyphen.
+fn is_stix_type(object_type: &str) -> bool {
+    (3..=250).contains(&object_type.len())
+        && object_type.starts_with(|c: char| c.is_ascii_lowercase())
+        && !object_type.ends_with('-')
+        && object_type
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Deserializes an optional STIX `confidence`, rejecting values outside 0 to 100.
+fn deserialize_confidence<'de, D>(deserializer: D) -> std::result::Result<Option<u8>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Option::<u64>::deserialize(deserializer)?
+        .map(|value| {
+            u8::try_from(value)
+                .ok()
+                .filter(|c| *c <= 100)
+                .ok_or_else(|| {
+                    serde::de::Error::custom(format!("confidence {value} is outside 0 to 100"))
+                })
+        })
+        .transpose()
+}
+
+/// A page of cyber threat indicators, as returned by the objects endpoint of a collection.
+pub type CCEnvelope = Envelope<CCIndicator>;
+
+/// Which classes of failures are retried when a retry policy is set with
+/// [`CCTaxiiClient::with_retry`].
+///
+/// The default retries all of them, since each usually clears up on its own: requests that
+/// time out, 502, 503, and 504 responses, 429 responses, and connections that fail. Turn a
+/// class off when retrying it is pointless for a deployment.
+///
+/// `POST` requests, such as those of `add_objects`, are not idempotent: the server may have
+/// partly applied one that timed out or failed with a 5xx, and sending it again could add
+/// the objects twice. They are therefore only retried on a 429, which means the server
+/// rejected the request without handling it.
+///
+/// # Fields
+///
+/// - `retry_on_timeout`: Retry requests that fail with `Timeout`.
+/// - `retry_on_5xx`: Retry 502, 503, and 504 responses.
+/// - `retry_on_429`: Retry 429 responses, waiting as long as their `Retry-After` asks, up to
+///   [`CCTaxiiClient::with_max_retry_after`].
+/// - `retry_on_connect_error`: Retry requests that fail with `TaxiiConnectionError`.
+///
+/// # Examples
+///
+/// ```
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+///     .with_retry(3, Duration::from_secs(1))
+///     .with_retry_policy(RetryPolicy {
+///         retry_on_5xx: false,
+///         ..RetryPolicy::default()
+///     });
+/// ```
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retry_on_timeout: bool,
+    pub retry_on_5xx: bool,
+    pub retry_on_429: bool,
+    pub retry_on_connect_error: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retry_on_timeout: true,
+            retry_on_5xx: true,
+            retry_on_429: true,
+            retry_on_connect_error: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Returns `true` if a `method` request failing with `error` should be retried.
+    fn retries(self, method: &str, error: &TaxiiError) -> bool {
+        if method == "POST" {
+            return self.retry_on_429 && matches!(error, TaxiiError::TaxiiRateLimited(_));
+        }
+        match error {
+            Timeout(_) => self.retry_on_timeout,
+            TaxiiConnectionError(_) => self.retry_on_connect_error,
+            TaxiiError::TaxiiRateLimited(_) => self.retry_on_429,
+            TaxiiError::TaxiiServerError(response) => {
+                self.retry_on_5xx && matches!(response.status(), 502..=504)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A value of the `match[version]` filter, selecting which versions of each object a server
+/// returns.
+///
+/// Servers silently return nothing for values the specification does not define, such as
+/// `latest`, so building the filter from this type, or passing it through
+/// [`VersionFilter::parse`], catches those mistakes before a request is sent.
+///
+/// # Examples
+///
+/// ```
+/// let mut matches = HashMap::new();
+/// matches.insert("version", VersionFilter::All.as_str());
+/// matches.insert("spec_version", "2.1");
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+/// let indicators = agent.get_cc_indicators(None, None, false, None, &Some(matches), true)?;
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VersionFilter<'a> {
+    /// The earliest version of each object (`first`).
+    First,
+    /// The latest version of each object (`last`), which servers return by default.
+    Last,
+    /// Every version of each object (`all`).
+    All,
+    /// The version whose `modified` timestamp is this RFC 3339 timestamp.
+    At(&'a str),
+}
+
+impl<'a> VersionFilter<'a> {
+    /// Parses a single `match[version]` value.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidFilter` if `value` is not `first`, `last`, `all`, or an RFC 3339
+    /// timestamp.
+    pub fn parse(value: &'a str) -> Result<Self> {
+        match value {
+            "first" => Ok(Self::First),
+            "last" => Ok(Self::Last),
+            "all" => Ok(Self::All),
+            _ if is_rfc3339(value) => Ok(Self::At(value)),
+            _ => Err(Box::new(InvalidFilter(format!(
+                "match[version] must be first, last, all, or a timestamp, got {value}"
+            )))),
+        }
+    }
+
+    /// Returns the query value sent for this filter.
+    #[must_use]
+    pub const fn as_str(&self) -> &'a str {
+        match self {
+            Self::First => "first",
+            Self::Last => "last",
+            Self::All => "all",
+            Self::At(timestamp) => timestamp,
+        }
+    }
+}
+
+/// Counts of how a pull was merged into an existing indicator store.
+///
+/// # Fields
+///
+/// - `inserted`: Indicators whose id was not yet in the store.
+/// - `updated`: Indicators that replaced an older version in the store.
+/// - `unchanged`: Indicators ignored because the store already held the same or a newer version.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct MergeReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+}
+
+/// Indicators retrieved by a pull that may have stopped before the last page.
+///
+/// # Fields
+///
+/// - `indicators`: The indicators retrieved before the pull stopped.
+/// - `next`: The pagination cursor of the first page that was not retrieved, if the pull
+///   stopped early.
+/// - `timed_out`: Whether the pull stopped because its deadline passed.
+/// - `cancelled`: Whether the pull stopped because its cancel flag was set.
+#[derive(Debug)]
+pub struct PartialIndicators {
+    pub indicators: Vec<CCIndicator>,
+    pub next: Option<String>,
+    pub timed_out: bool,
+    pub cancelled: bool,
+}
+
+/// The outcome of one step of a [`CCTaxiiClient::self_test`] run.
+///
+/// # Fields
 ///
-/// The Envelope is a container for objects in TAXII, potentially including additional
-/// pagination information.
+/// - `name`: The name of the step (`discovery`, `collections`, or `objects`).
+/// - `success`: Whether the step succeeded.
+/// - `elapsed`: How long the step took.
+/// - `error`: A description of the error, if the step failed.
+#[derive(Debug)]
+pub struct SelfTestStep {
+    pub name: &'static str,
+    pub success: bool,
+    pub elapsed: Duration,
+    pub error: Option<String>,
+}
+
+/// A report of a [`CCTaxiiClient::self_test`] run.
 ///
 /// # Fields
 ///
-/// - `more`: Indicates if more data is available (pagination).
-/// - `next`: The URL for the next set of data, if `more` is `true`.
-/// - `objects`: A collection of TAXII objects, each represented as a `HashMap<String, String>`.
-#[derive(Deserialize, Debug)]
-pub struct CCEnvelope {
-    more: Option<bool>,
-    next: Option<String>,
-    objects: Vec<CCIndicator>,
+/// - `steps`: The steps that were run, in order. Steps that depend on a failed step are not run.
+#[derive(Debug)]
+pub struct SelfTestReport {
+    pub steps: Vec<SelfTestStep>,
+}
+
+impl SelfTestReport {
+    /// Returns `true` if every step was run and succeeded.
+    #[must_use]
+    pub fn success(&self) -> bool {
+        self.steps.len() == 3 && self.steps.iter().all(|step| step.success)
+    }
+
+    /// Records the outcome of a step and returns its value, if it succeeded.
+    fn run<T>(&mut self, name: &'static str, step: impl FnOnce() -> Result<T>) -> Option<T> {
+        let start = Instant::now();
+        let result = step();
+        let elapsed = start.elapsed();
+        let (value, error) = match result {
+            Ok(value) => (Some(value), None),
+            Err(e) => (None, Some(format!("{e:?}"))),
+        };
+        self.steps.push(SelfTestStep {
+            name,
+            success: error.is_none(),
+            elapsed,
+            error,
+        });
+        value
+    }
 }
 
 /// A Custom TAXII client for interacting with the `CloudCover`TAXII server.
@@ -66,242 +662,6791 @@ pub struct CCEnvelope {
 /// This struct encapsulates the necessary details to make requests to a TAXII server,
 /// including the server's base URL and common headers.
 ///
+/// # Default collection
+///
+/// Methods taking an optional collection ID fall back to the default collection of the API
+/// root when it is `None`. TAXII 2.1 does not flag a default collection and servers may list
+/// collections in any order, so the collection with the lexicographically smallest ID among
+/// those serving STIX 2.1 objects is chosen, keeping repeated runs stable.
+///
 /// # Fields
 ///
-/// - `agent`: The HTTP agent used to send requests.
+/// - `agent`: The HTTP transport used to send requests.
 /// - `base_url`: The base URL of the TAXII server.
 /// - `common_headers`: Common HTTP headers included in every request.
 /// - `account`: Username/account name used for TAXII server authentification.
+/// - `private_authorization`: Authorization header used for private root requests, if set.
+/// - `verify_digest`: Whether response bodies are checked against the `Content-Digest` header.
+/// - `collections_path`: Path template of the collections endpoint.
+/// - `objects_path`: Path template of the objects endpoint of a collection.
+/// - `token_refresh`: Callback obtaining a fresh bearer token on a 401 during pagination.
+/// - `refreshed_authorization`: Authorization header built from the last refreshed token.
+/// - `warnings`: The last `MAX_WARNINGS` `Warning` header values received from the server,
+///   oldest first.
+/// - `collections_ttl`: How long listed collections are cached, or `None` to disable caching.
+/// - `collections_cache`: Cached collections and the time they were listed, keyed by root.
+/// - `default_added_after`: The `added_after` timestamp used by pulls that do not give one.
+/// - `strict_failures`: How many failing objects to report when a page fails to deserialize,
+///   or `None` to report only the first error.
+/// - `metrics`: The sink receiving request, error, and byte counts.
+/// - `max_url_length`: The maximum length of a request URL built from a list of ids.
+/// - `timeout`: How long a single request may take.
+/// - `retry_attempts`: How many times a request is attempted before a transient failure is
+///   returned.
+/// - `retry_backoff`: The base delay before retrying a transient failure.
+/// - `retry_policy`: Which classes of failures are retried.
+/// - `max_retry_after`: The longest `Retry-After` delay the client waits before a retry.
+/// - `cancel`: A flag that stops every paginated pull once set, if installed.
+/// - `clock_skew`: How many seconds the server clock was ahead of the local clock at the last
+///   response carrying a `Date` header.
+/// - `parallelism`: How many collections `get_all_indicators` pulls at the same time.
+/// - `dedup`: Whether `get_cc_indicators` keeps only the latest version of each indicator.
 pub struct CCTaxiiClient {
-    agent: Agent,
-    base_url: &'static str,
-    common_headers: Vec<(&'static str, String)>,
+    agent: Transport,
+    base_url: String,
+    common_headers: Vec<(String, String)>,
     account: String,
+    private_authorization: Option<String>,
+    verify_digest: bool,
+    collections_path: String,
+    objects_path: String,
+    token_refresh: Option<TokenRefresh>,
+    refreshed_authorization: RwLock<Option<String>>,
+    warnings: Mutex<Vec<String>>,
+    collections_ttl: Option<Duration>,
+    collections_cache: Mutex<HashMap<String, (Instant, Vec<Collection>)>>,
+    default_added_after: Option<String>,
+    strict_failures: Option<usize>,
+    metrics: Box<dyn Metrics>,
+    max_url_length: usize,
+    timeout: Duration,
+    retry_attempts: u32,
+    retry_backoff: Duration,
+    retry_policy: RetryPolicy,
+    max_retry_after: Duration,
+    cancel: Option<Arc<AtomicBool>>,
+    clock_skew: Mutex<Option<i64>>,
+    parallelism: usize,
+    dedup: bool,
+}
+
+/// A builder for a customized [`CCTaxiiClient`], created with [`CCTaxiiClient::builder`].
+///
+/// Options that are not set keep the defaults of `CCTaxiiClient::new`.
+///
+/// # Fields
+///
+/// - `username`: The username used to authenticate.
+/// - `api_key`: The API key or password used to authenticate.
+/// - `base_url`: The base URL of the TAXII server, if not the default.
+/// - `timeout`: How long a single request may take, if not the default.
+/// - `retry`: The number of attempts per request and the base backoff, if retries are enabled.
+/// - `retry_policy`: Which classes of failures are retried, if not all of them.
+/// - `user_agent`: The `User-Agent` header sent with every request, if set.
+/// - `extra_headers`: Other headers sent with every request.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone)]
+pub struct CCTaxiiClientBuilder {
+    username: String,
+    api_key: String,
+    base_url: Option<String>,
+    timeout: Option<Duration>,
+    retry: Option<(u32, Duration)>,
+    retry_policy: Option<RetryPolicy>,
+    user_agent: Option<String>,
+    extra_headers: Vec<(String, String)>,
+}
+
+impl CCTaxiiClientBuilder {
+    /// Sets the base URL of the TAXII server. See [`CCTaxiiClient::with_base_url`].
+    #[must_use]
+    pub fn base_url(mut self, base_url: impl Into<String>) -> Self {
+        self.base_url = Some(base_url.into());
+        self
+    }
+
+    /// Sets how long a single request may take. See [`CCTaxiiClient::with_timeout`].
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Retries transient failures up to `attempts` times in total. See
+    /// [`CCTaxiiClient::with_retry`].
+    #[must_use]
+    pub const fn retry(mut self, attempts: u32, base_backoff: Duration) -> Self {
+        self.retry = Some((attempts, base_backoff));
+        self
+    }
+
+    /// Sets which classes of failures are retried. See [`CCTaxiiClient::with_retry_policy`].
+    #[must_use]
+    pub const fn retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(policy);
+        self
+    }
+
+    /// Sets the `User-Agent` header sent with every request.
+    #[must_use]
+    pub fn user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = Some(user_agent.to_string());
+        self
+    }
+
+    /// Adds a header sent with every request, such as one required by a proxy. A header of
+    /// the same name, ignoring case, replaces the default or an earlier one.
+    #[must_use]
+    pub fn extra_header(mut self, name: &str, value: &str) -> Self {
+        self.extra_headers
+            .push((name.to_string(), value.to_string()));
+        self
+    }
+
+    /// Builds the client.
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidBaseUrl` if the base URL was set and is not a valid `http` or `https`
+    /// URL.
+    pub fn build(self) -> Result<CCTaxiiClient> {
+        let mut client = CCTaxiiClient::new(&self.username, &self.api_key);
+        if let Some(base_url) = self.base_url {
+            client = client.with_base_url(base_url)?;
+        }
+        if let Some(timeout) = self.timeout {
+            client = client.with_timeout(timeout);
+        }
+        if let Some((attempts, base_backoff)) = self.retry {
+            client = client.with_retry(attempts, base_backoff);
+        }
+        if let Some(policy) = self.retry_policy {
+            client = client.with_retry_policy(policy);
+        }
+        if let Some(user_agent) = self.user_agent {
+            client.set_header("User-Agent", &user_agent);
+        }
+        for (name, value) in self.extra_headers {
+            client.set_header(&name, &value);
+        }
+        Ok(client)
+    }
 }
 
 impl TaxiiClient for CCTaxiiClient {
     fn new(username: &str, api_key: &str) -> Self {
-        let key = format!("{username}:{api_key}");
-        let auth = format!("Basic {}", base64::encode(key.as_bytes()));
-        Self {
-            account: username.to_string(),
-            agent: Agent::new(),
-            base_url: "https://taxii2.cloudcover.net",
-            common_headers: vec![
-                (
-                    "Content-Type",
-                    "application/taxii+json;version=2.1".to_owned(),
-                ),
-                ("Accept", "application/taxii+json;version=2.1".to_owned()),
-                ("Authorization", auth),
-            ],
-        }
+        Self::with_normalized_base_url(username, api_key, DEFAULT_BASE_URL.to_string())
     }
 
-    fn request(&self, url: &str) -> Result<Response> {
+    fn request_method(&self, method: &str, url: &str, body: Option<&[u8]>) -> Result<Response> {
         let endpoint = format!("{}/{url}", self.base_url);
-        let request = self
+        let refreshed_auth = self
+            .refreshed_authorization
+            .read()
+            .ok()
+            .and_then(|auth| auth.clone());
+        let auth = refreshed_auth.as_deref().or_else(|| {
+            self.private_authorization
+                .as_deref()
+                .filter(|_| self.is_private_path(url))
+        });
+        let headers: Vec<(&str, &str)> = self
             .common_headers
             .iter()
-            .fold(self.agent.request("GET", &endpoint), |req, (key, value)| {
-                req.set(key, value)
+            .map(|(key, value)| match auth {
+                Some(auth) if key.eq_ignore_ascii_case("Authorization") => (key.as_str(), auth),
+                _ => (key.as_str(), value.as_str()),
             })
-            .timeout(Duration::from_secs(30));
-        match request.call() {
-            Ok(response) => Ok(response),
-            Err(ureq::Error::Status(code, response)) => match code {
-                401 => Err(Box::new(TaxiiAuthorizationError(response))),
-                404 => Err(Box::new(TaxiiNotFound(response))),
-                _ => Err(Box::new(TaxiiGenericError(response))),
-            },
-            Err(_) => Err(Box::new(TaxiiConnectionError(
-                "Request failed to execute".to_string(),
-            ))),
+            .collect();
+        let mut attempt = 1;
+        let response = loop {
+            self.metrics.on_request(method, url);
+            let result = self
+                .agent
+                .send(method, &endpoint, &headers, body, self.timeout)
+                .map_err(|e| {
+                    self.metrics.on_error(&e);
+                    e
+                });
+            match result {
+                Err(e)
+                    if attempt < self.retry_attempts && self.retry_policy.retries(method, &e) =>
+                {
+                    let delay = match &*e {
+                        TaxiiError::TaxiiRateLimited(response) => {
+                            response.header("Retry-After").and_then(parse_retry_after)
+                        }
+                        _ => None,
+                    };
+                    if delay.is_some_and(|delay| delay > self.max_retry_after) {
+                        return Err(e);
+                    }
+                    thread::sleep(delay.unwrap_or_else(|| backoff(self.retry_backoff, attempt)));
+                    attempt += 1;
+                }
+                result => break result?,
+            }
+        };
+        if let Some(date) = response.header("Date") {
+            if let (Ok(server_time), Ok(mut skew)) =
+                (httpdate::parse_http_date(date), self.clock_skew.lock())
+            {
+                let seconds =
+                    |duration: Duration| i64::try_from(duration.as_secs()).unwrap_or(i64::MAX);
+                *skew = Some(match server_time.duration_since(SystemTime::now()) {
+                    Ok(ahead) => seconds(ahead),
+                    Err(behind) => -seconds(behind.duration()),
+                });
+            }
+        }
+        let received = response.all("Warning");
+        if !received.is_empty() {
+            if let Ok(mut warnings) = self.warnings.lock() {
+                warnings.extend(received.into_iter().map(str::to_string));
+                let excess = warnings.len().saturating_sub(MAX_WARNINGS);
+                warnings.drain(..excess);
+            }
         }
+        Ok(response)
     }
 
     fn get_discovery(&self) -> Result<Discovery> {
         let response = self.request("taxii2/")?;
-        response
-            .into_json()
-            .map_err(|e| Box::new(JsonDeserializationError(e.to_string())))
+        self.parse_taxii_json(response)
     }
 
     fn get_collections(&self, root: Option<&str>) -> Result<Vec<String>> {
-        let collections_root =
-            root.map_or_else(|| "api".to_string(), std::string::ToString::to_string);
-        let collections_endpoint = format!("{collections_root}/collections/");
-        let response = self.request(&collections_endpoint)?;
-        let collections: Collections = response
-            .into_json()
-            .map_err(|e| JsonDeserializationError(e.to_string()))?;
-        Ok(collections.collections.into_iter().map(|c| c.id).collect())
+        let collections = self.cached_collections(root.unwrap_or("api"))?;
+        Ok(collections.into_iter().map(|c| c.id).collect())
+    }
+
+    fn private_root(&self) -> Option<&str> {
+        Some(self.account.as_str()).filter(|account| !account.is_empty())
+    }
+
+    fn objects_path(&self, root: &str, collection_id: &str) -> String {
+        self.objects_endpoint(root, collection_id)
     }
 }
 
 impl CCTaxiiClient {
-    /// Retrieves a list of cyber threat indicators from the `CloudCover` TAXII server.
+    /// Creates a client for the TAXII server at `base_url`, authenticating with the given
+    /// credentials.
     ///
-    /// This method fetches cyber threat indicators from a specified collection. It supports
-    /// filtering based on a timestamp, custom matches, and can optionally follow pagination
-    /// to retrieve all available indicators.
+    /// This is `new` for a server other than `https://taxii2.cloudcover.net`, such as a
+    /// staging or on-prem deployment. The URL is checked as by
+    /// [`with_base_url`](Self::with_base_url), the chaining method that retargets an existing
+    /// client, whose name this constructor cannot share.
     ///
     /// # Parameters
     ///
-    /// - `collection_id`: An optional reference to a string representing the collection ID
-    ///   from which to retrieve indicators. If `None`, the first available collection ID is used.
+    /// - `username`: The username used to authenticate, which is also the private API root.
+    /// - `api_key`: The API key or password used to authenticate.
+    /// - `base_url`: The base URL of the TAXII server (e.g., `https://taxii.example.com`).
     ///
-    /// - `limit`: An optional usize value representing the maximum number of indicators to
-    ///   retrieve in a single request. Defaults to 1000 if `None`.
+    /// # Examples
     ///
-    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
-    ///   or the public API root (`false`).
+    /// ```
+    /// let agent = CCTaxiiClient::new_with_base_url(
+    ///     "my_username",
+    ///     "my_api_key",
+    ///     "https://taxii-mirror.internal:8443",
+    /// )?;
+    /// ```
     ///
-    /// - `added_after`: An optional reference to a string representing a timestamp. If provided,
-    ///   only indicators added after this timestamp will be retrieved.
+    /// # Errors
     ///
-    /// - `matches`: A reference to an optional `HashMap` with filter criteria in the form
-    ///   of key-value pairs. The keys and values are references to strings.
+    /// Returns `InvalidBaseUrl` under the same conditions as `with_base_url`.
+    pub fn new_with_base_url(
+        username: &str,
+        api_key: &str,
+        base_url: impl Into<String>,
+    ) -> Result<Self> {
+        let base_url = normalize_base_url(&base_url.into())?;
+        Ok(Self::with_normalized_base_url(username, api_key, base_url))
+    }
+
+    /// Creates a client for an already normalized base URL. Both constructors end here.
+    fn with_normalized_base_url(username: &str, api_key: &str, base_url: String) -> Self {
+        let auth = basic_auth(username, api_key);
+        Self {
+            account: username.to_string(),
+            agent: Transport::new(),
+            base_url,
+
+            common_headers: vec![
+                (
+                    "Content-Type".to_owned(),
+                    "application/taxii+json;version=2.1".to_owned(),
+                ),
+                (
+                    "Accept".to_owned(),
+                    "application/taxii+json;version=2.1".to_owned(),
+                ),
+                ("Authorization".to_owned(), auth),
+            ],
+            private_authorization: None,
+            verify_digest: false,
+            collections_path: "{root}/collections/".to_string(),
+            objects_path: "{root}/collections/{collection}/objects/".to_string(),
+            token_refresh: None,
+            refreshed_authorization: RwLock::new(None),
+            warnings: Mutex::new(Vec::new()),
+            collections_ttl: None,
+            collections_cache: Mutex::new(HashMap::new()),
+            default_added_after: None,
+            strict_failures: None,
+            metrics: Box::new(NoMetrics),
+            max_url_length: DEFAULT_MAX_URL_LENGTH,
+            timeout: Duration::from_secs(30),
+            retry_attempts: 1,
+            retry_backoff: Duration::ZERO,
+            retry_policy: RetryPolicy::default(),
+            max_retry_after: Duration::from_secs(60),
+            cancel: None,
+            clock_skew: Mutex::new(None),
+            parallelism: 4,
+            dedup: false,
+        }
+    }
+
+    /// Starts building a client with the given credentials and further options.
     ///
-    /// - `follow_pages`: A boolean flag indicating whether to follow pagination links to retrieve
-    ///   additional indicators beyond the initial request (`true`), or to only retrieve the indicators
-    ///   from the initial request (`false`).
+    /// `new` covers the common case. The builder gathers the other options in one place and
+    /// checks them when [`build`](CCTaxiiClientBuilder::build) is called.
     ///
-    /// # Returns
+    /// # Parameters
     ///
-    /// Returns a `Result<Vec<CCIndicator>>` which is either:
-    /// - `Ok(Vec<CCIndicator>)` containing the list of retrieved indicators.
-    /// - `Err(Box<TaxiiError>)` indicating an error occurred during the retrieval process.
+    /// - `username`: The username used to authenticate, which is also the private API root.
+    /// - `api_key`: The API key or password used to authenticate.
     ///
     /// # Examples
     ///
     /// ```
-    /// mut matches = std::Collections::HashMap::new();
-    /// matches.insert("type", "indicator");
-    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
-    /// let indicators_result = agent.get_cc_indicators(
-    ///     Some("collection_id"),
-    ///     Some(500),
-    ///     true,
-    ///     Some("2024-01-01T00:00:00Z"),
-    ///     &Some(matches),
-    ///     true
-    /// );
+    /// let agent = CCTaxiiClient::builder("my_username", "my_api_key")
+    ///     .base_url("https://taxii-mirror.internal:8443")
+    ///     .timeout(Duration::from_secs(120))
+    ///     .user_agent("acme-soar/1.0")
+    ///     .build()?;
+    /// ```
+    #[must_use]
+    pub fn builder(username: &str, api_key: &str) -> CCTaxiiClientBuilder {
+        CCTaxiiClientBuilder {
+            username: username.to_string(),
+            api_key: api_key.to_string(),
+            base_url: None,
+            timeout: None,
+            retry: None,
+            retry_policy: None,
+            user_agent: None,
+            extra_headers: Vec::new(),
+        }
+    }
+
+    /// Creates a client authenticating with `Authorization: Bearer <token>` instead of Basic
+    /// credentials, for servers protected by OAuth or another token scheme.
     ///
-    /// match indicators_result {
-    ///     Ok(indicators) => {
-    ///         // Process the list of indicators
-    ///     }
-    ///     Err(error) => {
-    ///         // Handle the error
+    /// Basic credentials name the account, whose private API root is used by pulls with
+    /// `private` set. A token does not, so give the account here if private pulls are needed.
+    /// Without one `private_root` is `None`, and calls with `private` set fail with
+    /// `TaxiiCollectionError` before any request is sent.
+    ///
+    /// # Parameters
+    ///
+    /// - `token`: The bearer token.
+    /// - `account`: The account name, which is also the private API root, if any.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::with_bearer_token("my_token", Some("my_account"));
+    /// ```
+    #[must_use]
+    pub fn with_bearer_token(token: &str, account: Option<&str>) -> Self {
+        let mut client = Self::new("", "");
+        client.account = account.unwrap_or_default().to_string();
+        client.set_header("Authorization", &format!("Bearer {token}"));
+        client
+    }
+
+    /// Points the client at another TAXII server, such as a staging or on-prem deployment, an
+    /// air-gapped mirror of the feed, or a test server. Clients default to
+    /// `https://taxii2.cloudcover.net`.
+    ///
+    /// The URL is checked here so that a typo fails at build time rather than as a cryptic
+    /// connection error on the first request. It must use the `http` or `https` scheme and
+    /// name a host, and may include a port and a path prefix. Trailing slashes are removed so
+    /// endpoint paths join cleanly.
+    ///
+    /// # Parameters
+    ///
+    /// - `base_url`: The base URL of the TAXII server (e.g., `https://taxii.example.com`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_base_url("https://taxii-mirror.internal:8443/")?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `InvalidBaseUrl` if the scheme is not `http` or `https`, the host is missing or
+    /// malformed, the port is not a number, or the URL has a query or fragment.
+    pub fn with_base_url(mut self, base_url: impl Into<String>) -> Result<Self> {
+        self.base_url = normalize_base_url(&base_url.into())?;
+        Ok(self)
+    }
+
+    /// Sets separate credentials for requests made against the private account root.
+    ///
+    /// Some setups use one credential for the public `api` root and another for the private
+    /// account root. Once set, requests whose path starts with the private root are sent with
+    /// these credentials, while all other requests keep using the credentials given to `new`.
+    /// The private root becomes the `username` given here.
+    ///
+    /// # Parameters
+    ///
+    /// - `username`: The username of the private account, which is also its API root.
+    /// - `api_key`: The API key or password for the private account.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("public_username", "public_api_key")
+    ///     .with_private_credentials("private_username", "private_api_key");
+    /// ```
+    #[must_use]
+    pub fn with_private_credentials(mut self, username: &str, api_key: &str) -> Self {
+        self.account = username.to_string();
+        self.private_authorization = Some(basic_auth(username, api_key));
+        self
+    }
+
+    /// Sets a callback used to obtain a fresh bearer token when one expires mid-pull.
+    ///
+    /// Long pulls may outlive a short-lived token. When a page request fails with a 401 while
+    /// paginating, the callback is called for a new token, the `Authorization` header becomes
+    /// `Bearer <token>` for all later requests, including those to the private root when
+    /// [`with_private_credentials`](Self::with_private_credentials) is also set, and the page
+    /// is retried once. A second 401 for the same page is returned as an error.
+    ///
+    /// # Parameters
+    ///
+    /// - `refresh`: A callback returning a fresh bearer token.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_token_refresh(|| fetch_new_token());
+    /// ```
+    #[must_use]
+    pub fn with_token_refresh<F>(mut self, refresh: F) -> Self
+    where
+        F: Fn() -> String + Send + Sync + 'static,
+    {
+        self.token_refresh = Some(Box::new(refresh));
+        self
+    }
+
+    /// Requests a page, refreshing the bearer token and retrying once on a 401 if a
+    /// `token_refresh` callback is set.
+    fn request_page(&self, url: &str) -> Result<Response> {
+        match (self.request(url), &self.token_refresh) {
+            (Err(e), Some(refresh)) if matches!(*e, TaxiiAuthorizationError(_)) => {
+                let auth = format!("Bearer {}", refresh());
+                if let Ok(mut refreshed) = self.refreshed_authorization.write() {
+                    *refreshed = Some(auth);
+                }
+                self.request(url)
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Caches the collections listed by `get_collections` for `ttl`, per API root.
+    ///
+    /// Collection lists rarely change, so repeated calls for the same root within `ttl` are
+    /// answered from memory instead of the network. Caching is disabled by default.
+    ///
+    /// # Parameters
+    ///
+    /// - `ttl`: How long a listed set of collections stays valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_collections_cache_ttl(Duration::from_secs(300));
+    /// ```
+    #[must_use]
+    pub const fn with_collections_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.collections_ttl = Some(ttl);
+        self
+    }
+
+    /// Sets a baseline `added_after` timestamp for every pull that does not give its own.
+    ///
+    /// This suits a client dedicated to incremental monitoring. Methods that take an
+    /// `added_after` parameter use this timestamp when it is `None`, while an explicit
+    /// per-call value still takes precedence. Methods without the parameter, such as
+    /// `dump_collection_bundle`, `snapshot_collection`, `get_cc_indicators_by_ids`, and
+    /// `estimate_collection_size`, always cover the whole collection.
+    ///
+    /// # Parameters
+    ///
+    /// - `timestamp`: The default `added_after` timestamp (e.g., `2024-01-01T00:00:00Z`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_default_added_after("2024-01-01T00:00:00Z");
+    /// ```
+    #[must_use]
+    pub fn with_default_added_after(mut self, timestamp: &str) -> Self {
+        self.default_added_after = Some(timestamp.to_string());
+        self
+    }
+
+    /// Reports every failing object, up to `max_reported`, when a page of indicators fails to
+    /// deserialize.
+    ///
+    /// A single malformed object always fails the whole pull. By default the error only
+    /// carries the first deserialization message. In strict mode each object of the failing
+    /// page is checked, and the `JsonDeserializationError` lists the id, error, and raw JSON
+    /// of up to `max_reported` offending objects, so integrity-critical consumers can see
+    /// exactly what was rejected. Pages are kept as raw JSON while parsing, which costs some
+    /// extra memory and time.
+    ///
+    /// # Parameters
+    ///
+    /// - `max_reported`: The maximum number of failing objects named in the error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key").with_strict_deserialization(5);
+    /// ```
+    #[must_use]
+    pub const fn with_strict_deserialization(mut self, max_reported: usize) -> Self {
+        self.strict_failures = Some(max_reported);
+        self
+    }
+
+    /// Identifies the integration to the server with an `X-TAXII-Client-Name` header.
+    ///
+    /// The header is sent on every request, which lets the server operator attribute requests
+    /// and quota to the integration when debugging. Calling this again replaces the name.
+    ///
+    /// # Parameters
+    ///
+    /// - `name`: The name of the integration.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key").with_client_name("acme-soar");
+    /// ```
+    #[must_use]
+    pub fn with_client_name(mut self, name: &str) -> Self {
+        self.set_header("X-TAXII-Client-Name", name);
+        self
+    }
+
+    /// Adds a header to every request, replacing any header of the same name, ignoring case.
+    fn set_header(&mut self, name: &str, value: &str) {
+        self.common_headers
+            .retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+        self.common_headers
+            .push((name.to_string(), value.to_string()));
+    }
+
+    /// Sets how long a single request may take before it fails with `Timeout`.
+    ///
+    /// The default of 30 seconds can be too short for a large page from a slow server, or
+    /// too long when failing fast matters more. The limit applies to each request, so a
+    /// paginated pull may take longer in total.
+    ///
+    /// # Parameters
+    ///
+    /// - `timeout`: The time limit of each request.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent =
+    ///     CCTaxiiClient::new("my_username", "my_api_key").with_timeout(Duration::from_secs(120));
+    /// ```
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Retries requests that fail transiently, with exponential backoff and jitter.
+    ///
+    /// A request failing with a 429, 502, 503, or 504 status, a timeout, or a connection error
+    /// is sent again, up to `attempts` times in total, so one blip does not fail a long paginated
+    /// pull. Before retry `n` the client waits between half and all of `base_backoff * 2^(n - 1)`,
+    /// chosen at random so that clients do not retry in lockstep. A 429 response's
+    /// `Retry-After` header, in seconds or as an HTTP date, overrides this delay, unless it
+    /// exceeds [`with_max_retry_after`](Self::with_max_retry_after). Once the
+    /// attempts are exhausted the last error is returned unchanged, as `TaxiiRateLimited`
+    /// for a 429. Other errors are never retried, `POST` requests are only retried on a 429
+    /// (see [`RetryPolicy`]), and [`with_retry_policy`](Self::with_retry_policy) narrows which
+    /// of these classes are. By default each request is attempted once.
+    ///
+    /// # Parameters
+    ///
+    /// - `attempts`: The total number of attempts per request, including the first.
+    /// - `base_backoff`: The delay before the first retry, doubled for each later retry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_retry(5, Duration::from_millis(500));
+    /// ```
+    #[must_use]
+    pub const fn with_retry(mut self, attempts: u32, base_backoff: Duration) -> Self {
+        self.retry_attempts = attempts;
+        self.retry_backoff = base_backoff;
+        self
+    }
+
+    /// Sets which classes of failures are retried once retries are enabled with
+    /// [`with_retry`](Self::with_retry). By default every transient failure is retried.
+    ///
+    /// # Parameters
+    ///
+    /// - `policy`: The classes of failures to retry.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_retry(3, Duration::from_secs(1))
+    ///     .with_retry_policy(RetryPolicy {
+    ///         retry_on_429: false,
+    ///         ..RetryPolicy::default()
+    ///     });
+    /// ```
+    #[must_use]
+    pub const fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    /// Installs a cancel token checked before every page request of a paginated pull.
+    ///
+    /// Setting the flag from another thread stops the pulls of `get_cc_indicators`,
+    /// `iter_cc_indicators`, the dump and stream methods, `get_all_indicators`, and every other
+    /// method that follows pages before they request their next page. Only
+    /// [`get_cc_indicators_partial`](Self::get_cc_indicators_partial) keeps the data fetched
+    /// so far, returning it with its `cancelled` flag set. Every other method discards it and
+    /// fails with `Cancelled`. Clear the flag to use the client again.
+    ///
+    /// # Parameters
+    ///
+    /// - `cancel`: The flag that cancels pulls once set.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key").with_cancel_token(Arc::clone(&cancel));
+    /// // From another thread: cancel.store(true, Ordering::SeqCst);
+    /// ```
+    #[must_use]
+    pub fn with_cancel_token(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Returns `true` if the client's cancel token is installed and set.
+    fn is_cancelled(&self) -> bool {
+        self.cancel
+            .as_ref()
+            .is_some_and(|flag| flag.load(atomic::Ordering::SeqCst))
+    }
+
+    /// Sets the longest delay a 429 response's `Retry-After` header may ask for before a retry.
+    ///
+    /// The delay comes from the server, and sleeping on a value such as a day would block the
+    /// calling thread for that long. When a 429 asks for a longer wait than `max_delay`, the
+    /// request is not retried and fails with `TaxiiRateLimited` right away, so the caller can
+    /// reschedule it. The default is 60 seconds.
+    ///
+    /// # Parameters
+    ///
+    /// - `max_delay`: The longest `Retry-After` delay to wait for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_retry(5, Duration::from_millis(500))
+    ///     .with_max_retry_after(Duration::from_secs(300));
+    /// ```
+    #[must_use]
+    pub const fn with_max_retry_after(mut self, max_delay: Duration) -> Self {
+        self.max_retry_after = max_delay;
+        self
+    }
+
+    /// Sets the maximum length of a request URL built from a list of ids.
+    ///
+    /// Servers and proxies reject URLs beyond a certain length, so `get_cc_indicators_by_ids`
+    /// splits its `match[id]` filter across as many requests as needed to keep each URL,
+    /// including the base URL, within this length. The default is 4096 bytes. An id too long
+    /// to fit on its own is still sent, alone in its request.
+    ///
+    /// # Parameters
+    ///
+    /// - `max_length`: The maximum URL length in bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key").with_max_url_length(2048);
+    /// ```
+    #[must_use]
+    pub const fn with_max_url_length(mut self, max_length: usize) -> Self {
+        self.max_url_length = max_length;
+        self
+    }
+
+    /// Sets how many collections `get_all_indicators` pulls at the same time.
+    ///
+    /// Each collection is pulled on its own thread, with at most `parallelism` threads running
+    /// at once. The default is 4. A value of 0 is treated as 1, which pulls one collection at a
+    /// time.
+    ///
+    /// # Parameters
+    ///
+    /// - `parallelism`: The maximum number of collections pulled concurrently.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key").with_parallelism(8);
+    /// ```
+    #[must_use]
+    pub const fn with_parallelism(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism;
+        self
+    }
+
+    /// Installs a sink receiving metrics about the client's requests.
+    ///
+    /// The sink is told about every request sent, every request or parsing error, and the
+    /// number of response body bytes read. By default metrics are discarded.
+    ///
+    /// # Parameters
+    ///
+    /// - `metrics`: The sink implementing `Metrics`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key").with_metrics(my_sink);
+    /// ```
+    #[must_use]
+    pub fn with_metrics<M: Metrics + 'static>(mut self, metrics: M) -> Self {
+        self.metrics = Box::new(metrics);
+        self
+    }
+
+    /// Returns how many seconds the server clock is ahead of the local clock, negative if it is
+    /// behind.
+    ///
+    /// The skew is measured from the `Date` header of the most recent response that had one,
+    /// so it is `None` until such a response arrives. `Date` has a resolution of one second
+    /// and is set before the response travels back, so expect an error of about a second
+    /// plus the response latency. A large skew means `added_after` windows computed from the
+    /// local clock can miss data, and should be shifted by the skew.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// agent.get_discovery()?;
+    /// if let Some(skew) = agent.clock_skew() {
+    ///     if skew.abs() > 60 {
+    ///         eprintln!("Server clock is off by {skew}s");
     ///     }
     /// }
     /// ```
+    #[must_use]
+    pub fn clock_skew(&self) -> Option<i64> {
+        self.clock_skew.lock().ok().and_then(|skew| *skew)
+    }
+
+    /// Retrieves the collections of an API root with all their details.
+    ///
+    /// Unlike `get_collections`, which returns only the IDs, this keeps each collection's
+    /// title, name, access flags, and media types. The result is never cached.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The API root whose collections are listed (e.g., `api`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// for collection in agent.get_collections_detailed("api")? {
+    ///     println!("{} (writable: {})", collection.title, collection.can_write);
+    /// }
+    /// ```
     ///
     /// # Errors
     ///
-    /// This method can return various error types encapsulated within `TaxiiError`, such as:
-    /// - `TaxiiCollectionError` if no collection is available or specified collection ID is invalid.
-    /// - `JsonDeserializationError` if there is an error in parsing the response from the server.
+    /// - Returns `UnexpectedContentType` if the server answers with a body that is not JSON,
+    ///   such as the HTML page of a captive portal.
+    /// - Returns `JsonDeserializationError` if the response cannot be parsed.
     /// - Other errors related to network connectivity or server responses.
-    pub fn get_cc_indicators(
-        &self,
-        collection_id: Option<&str>,
-        limit: Option<usize>,
-        private: bool,
-        added_after: Option<&str>,
-        matches: &Option<HashMap<&str, &str>>,
-        follow_pages: bool,
-    ) -> Result<Vec<CCIndicator>> {
-        let root = if private { &self.account } else { "api" };
-        let collection = match collection_id {
-            Some(id) => id.to_string(),
-            None => self
-                .get_collections(Some(root))?
-                .first()
-                .ok_or_else(|| {
-                    Box::new(TaxiiCollectionError("No collections available".to_string()))
-                })?
-                .to_string(),
-        };
-        let limit = limit.unwrap_or(1000);
-        let mut url = format!("{root}/collections/{collection}/objects/?limit={limit}");
-        if let Some(timestamp) = added_after {
-            url += &format!("&added_after={timestamp}");
-        }
-        let match_query = matches.as_ref().map_or(String::new(), |match_filters| {
-            match_filters
-                .iter()
-                .fold(String::new(), |acc, (k, v)| format!("{acc}&match[{k}]={v}"))
-        });
-        url += &match_query;
-        let mut all_indicators: Vec<CCIndicator> = Vec::new();
-        let mut more = true;
-        while more {
-            let response = self.request(&url)?;
-            let envelope: CCEnvelope = response
-                .into_json()
-                .map_err(|e| JsonDeserializationError(e.to_string()))?;
-            all_indicators.extend(envelope.objects);
-            more = follow_pages && envelope.more.unwrap_or(false);
-            if let Some(next_url) = envelope.next {
-                url += &format!("&next={next_url}");
-            } else {
-                break;
+    pub fn get_collections_detailed(&self, root: &str) -> Result<Vec<Collection>> {
+        let endpoint = self.collections_path.replace(ROOT_PLACEHOLDER, root);
+        let response = self.request(&endpoint)?;
+        let collections: Collections = self.parse_taxii_json(response)?;
+        Ok(collections.collections)
+    }
+
+    /// Retrieves the discovery information and the detailed collections of the public or
+    /// private API root together, such as for a UI showing a server overview.
+    ///
+    /// The two requests are sent one after the other.
+    ///
+    /// # Parameters
+    ///
+    /// - `private`: A boolean flag indicating whether to list the collections of the private
+    ///   API root (`true`) or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let overview = agent.overview(false)?;
+    /// println!("{}: {} collections", overview.discovery.title, overview.collections.len());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `get_discovery` and `get_collections_detailed`.
+    pub fn overview(&self, private: bool) -> Result<ServerOverview> {
+        Ok(ServerOverview {
+            discovery: self.get_discovery()?,
+            collections: self.get_collections_detailed(self.root(private)?)?,
+        })
+    }
+
+    /// Clears the cached collections of every root, so the next `get_collections` call for
+    /// each root requests the server again.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// agent.invalidate_collections_cache();
+    /// ```
+    pub fn invalidate_collections_cache(&self) {
+        if let Ok(mut cache) = self.collections_cache.lock() {
+            cache.clear();
+        }
+    }
+
+    /// Returns the `Warning` header values the server has sent so far, oldest first.
+    ///
+    /// TAXII servers may announce deprecations or partial results through `Warning` headers.
+    /// Every such header on a successful response is logged here so callers can notice them
+    /// before an endpoint breaks. Only the 100 most recent values are kept, so a long-running
+    /// client does not grow without bound; drain them regularly with
+    /// [`take_warnings`](Self::take_warnings) to see every one.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let indicators = agent.get_cc_indicators(None, None, false, None, &None, true)?;
+    /// for warning in agent.warnings() {
+    ///     eprintln!("TAXII server warning: {warning}");
+    /// }
+    /// ```
+    #[must_use]
+    pub fn warnings(&self) -> Vec<String> {
+        self.warnings
+            .lock()
+            .map(|warnings| warnings.clone())
+            .unwrap_or_default()
+    }
+
+    /// Removes and returns the logged `Warning` header values, oldest first.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let warnings = agent.take_warnings();
+    /// ```
+    pub fn take_warnings(&self) -> Vec<String> {
+        self.warnings
+            .lock()
+            .map(|mut warnings| std::mem::take(&mut *warnings))
+            .unwrap_or_default()
+    }
+
+    /// Retrieves a single indicator from a collection by its STIX id.
+    ///
+    /// This requests the object's own endpoint instead of listing the collection. If the
+    /// server returns several versions of the object, the most recently modified one is
+    /// returned.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: The ID of the collection holding the object.
+    ///
+    /// - `object_id`: The STIX id of the indicator.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let indicator = agent.get_object(
+    ///     "c1",
+    ///     "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+    ///     false,
+    /// )?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `TaxiiNotFound` if the object does not exist.
+    /// - Returns `JsonDeserializationError` if the response cannot be parsed or holds no
+    ///   object.
+    /// - Other errors related to network connectivity or server responses.
+    pub fn get_object(
+        &self,
+        collection_id: &str,
+        object_id: &str,
+        private: bool,
+    ) -> Result<CCIndicator> {
+        let url = format!(
+            "{}{object_id}/",
+            self.objects_endpoint(self.root(private)?, collection_id)
+        );
+        let response = self.request(&url)?;
+        let envelope: CCEnvelope = self.parse_json(response)?;
+        envelope
+            .objects
+            .into_iter()
+            .max_by(|a, b| compare_timestamps(&a.modified, &b.modified))
+            .ok_or_else(|| {
+                Box::new(JsonDeserializationError(format!(
+                    "No object returned for {object_id}"
+                )))
+            })
+    }
+
+    /// Lists the versions of an object in a collection, as the `modified` timestamps the
+    /// server holds for it.
+    ///
+    /// Comparing these against a stored `modified` value shows whether an indicator has been
+    /// revised since it was pulled. The timestamps are returned in the order the server sends
+    /// them, which the TAXII specification leaves unspecified.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: The ID of the collection holding the object.
+    ///
+    /// - `object_id`: The STIX id of the object.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let versions = agent.get_object_versions("c1", &indicator.id, false)?;
+    /// if versions.iter().any(|version| version > &indicator.modified) {
+    ///     let newest = agent.get_object("c1", &indicator.id, false)?;
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `TaxiiNotFound` if the object does not exist.
+    /// - Returns `JsonDeserializationError` if the response cannot be parsed.
+    /// - Other errors related to network connectivity or server responses.
+    pub fn get_object_versions(
+        &self,
+        collection_id: &str,
+        object_id: &str,
+        private: bool,
+    ) -> Result<Vec<String>> {
+        #[derive(Deserialize)]
+        struct Versions {
+            #[serde(default)]
+            versions: Vec<String>,
+        }
+
+        let url = format!(
+            "{}{object_id}/versions/",
+            self.objects_endpoint(self.root(private)?, collection_id)
+        );
+        let response = self.request(&url)?;
+        let versions: Versions = self.parse_json(response)?;
+        Ok(versions.versions)
+    }
+
+    /// Deletes an object from a writable collection.
+    ///
+    /// Every version of the object is deleted unless `version` selects some, as a
+    /// `match[version]` filter.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: The ID of the collection holding the object.
+    ///
+    /// - `object_id`: The STIX id of the object.
+    ///
+    /// - `version`: The versions to delete, as a [`VersionFilter`] value such as `first` or a
+    ///   `modified` timestamp. If `None`, every version is deleted.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// agent.delete_object("c1", &indicator.id, Some(&indicator.modified), true)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `InvalidFilter` if `version` is not a valid `match[version]` value.
+    /// - Returns `TaxiiNotFound` if the object or collection does not exist.
+    /// - Other errors related to network connectivity or server responses, such as
+    ///   `TaxiiGenericError` with status 403 when the collection is not writable.
+    pub fn delete_object(
+        &self,
+        collection_id: &str,
+        object_id: &str,
+        version: Option<&str>,
+        private: bool,
+    ) -> Result<()> {
+        let mut url = format!(
+            "{}{object_id}/",
+            self.objects_endpoint(self.root(private)?, collection_id)
+        );
+        if let Some(version) = version {
+            url = format!(
+                "{url}?match[version]={}",
+                utf8_percent_encode(VersionFilter::parse(version)?.as_str(), NON_ALPHANUMERIC)
+            );
+        }
+        self.request_method("DELETE", &url, None)?;
+        Ok(())
+    }
+
+    /// Retrieves the cyber threat indicators with the given STIX ids, following every page.
+    ///
+    /// The ids are sent as a comma-separated `match[id]` filter. When the URL would exceed
+    /// the maximum set with [`with_max_url_length`](Self::with_max_url_length), the ids are
+    /// split across several requests and the results merged in the order of the ids. No
+    /// request is made when `ids` is empty.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `ids`: The STIX ids of the indicators to retrieve.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let indicators = agent.get_cc_indicators_by_ids(
+    ///     None,
+    ///     false,
+    ///     &["indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f"],
+    /// )?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_cc_indicators_by_ids(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        ids: &[&str],
+    ) -> Result<Vec<CCIndicator>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let root = self.root(private)?;
+        let collection = self.resolve_collection(collection_id, root)?;
+        let base = self.collection_objects_url(root, &collection, None, None, None);
+        let budget = self
+            .max_url_length
+            .saturating_sub(self.base_url.len() + 1 + base.len() + "&match[id]=".len());
+        let mut chunks: Vec<String> = Vec::new();
+        for id in ids {
+            match chunks.last_mut() {
+                Some(chunk) if chunk.len() + 1 + id.len() <= budget => {
+                    chunk.push(',');
+                    chunk.push_str(id);
+                }
+                _ => chunks.push((*id).to_string()),
+            }
+        }
+        let mut indicators: Vec<CCIndicator> = Vec::new();
+        for chunk in chunks {
+            let matches = HashMap::from([("id", chunk.as_str())]);
+            let url = self.collection_objects_url(root, &collection, None, None, Some(&matches));
+            self.fetch_pages(&url, true, |page: CCEnvelope| {
+                indicators.extend(page.objects);
+                Ok(true)
+            })?;
+        }
+        Ok(indicators)
+    }
+
+    /// Retrieves only the indicators that are new or changed since a previous sync.
+    ///
+    /// The collection's manifest is read and each object's `version` is compared against
+    /// `known_versions`, a map of STIX id to the version seen in the previous sync. Only the
+    /// objects whose version differs, or which are not in the map, are then fetched. This is
+    /// more precise than an `added_after` window.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `known_versions`: The id to version map returned by the previous sync. Pass an empty
+    ///   map for the first sync.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok((indicators, versions))` with the new or changed indicators and the id to
+    /// version map of the collection as listed in the manifest, to be stored for the next sync.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let (indicators, versions) = agent.sync_cc_indicators(None, false, &HashMap::new())?;
+    /// let (changed, versions) = agent.sync_cc_indicators(None, false, &versions)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`, for both the manifest and the objects.
+    pub fn sync_cc_indicators(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        known_versions: &HashMap<String, String>,
+    ) -> Result<(Vec<CCIndicator>, HashMap<String, String>)> {
+        let root = self.root(private)?;
+        let collection = self.resolve_collection(collection_id, root)?;
+        let records = self.manifest_records(root, &collection, None)?;
+        let changed: Vec<&str> = records
+            .iter()
+            .filter(|record| known_versions.get(&record.id) != Some(&record.version))
+            .map(|record| record.id.as_str())
+            .collect();
+        let indicators = self.get_cc_indicators_by_ids(Some(&collection), private, &changed)?;
+        let versions = records
+            .iter()
+            .map(|record| (record.id.clone(), record.version.clone()))
+            .collect();
+        Ok((indicators, versions))
+    }
+
+    /// Retrieves the indicators added to a collection after the object with id `last_seen_id`.
+    ///
+    /// The watermark id is looked up in the collection's manifest to resolve the date it was
+    /// added, and only the indicators added after that date are pulled, following every page.
+    /// Objects added at exactly the same time as the watermark are not returned. If
+    /// `last_seen_id` is `None`, or it is no longer listed in the manifest, the whole
+    /// collection is returned so nothing is skipped.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `last_seen_id`: The STIX id of the last object seen by the caller.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Vec<CCIndicator>)` oldest first, as ordered by the server. The id of the
+    /// last indicator is the watermark to store for the next pull; keep the previous one if
+    /// the result is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let indicators = agent.get_cc_indicators_after_watermark(None, false, Some(&watermark))?;
+    /// if let Some(last) = indicators.last() {
+    ///     watermark = last.id.clone();
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`, for both the manifest and the objects.
+    pub fn get_cc_indicators_after_watermark(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        last_seen_id: Option<&str>,
+    ) -> Result<Vec<CCIndicator>> {
+        let root = self.root(private)?;
+        let collection = self.resolve_collection(collection_id, root)?;
+        let added_after = match last_seen_id {
+            Some(id) => {
+                let url = self.manifest_url(root, &collection, 1, None)
+                    + &match_query(&HashMap::from([("id", id)]));
+                let response = self.request(&url)?;
+                let manifest: Manifest = self.parse_json(response)?;
+                manifest
+                    .objects
+                    .into_iter()
+                    .flatten()
+                    .find(|record| record.id == id)
+                    .map(|record| record.date_added)
+            }
+            None => None,
+        };
+        let url =
+            self.collection_objects_url(root, &collection, None, added_after.as_deref(), None);
+        let mut indicators: Vec<CCIndicator> = Vec::new();
+        self.fetch_pages(&url, true, |page: CCEnvelope| {
+            indicators.extend(page.objects);
+            Ok(true)
+        })?;
+        Ok(indicators)
+    }
+
+    /// Checks whether any object was added to a collection after `added_after`.
+    ///
+    /// Only a single manifest record is requested, which makes this much cheaper than a pull
+    /// when polling for new data.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `added_after`: The timestamp to check for newer objects.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// if agent.has_new_since(None, false, "2024-01-01T00:00:00Z")? {
+    ///     // Pull the new indicators
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn has_new_since(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        added_after: &str,
+    ) -> Result<bool> {
+        let root = self.root(private)?;
+        let collection = self.resolve_collection(collection_id, root)?;
+        let url = self.manifest_url(root, &collection, 1, Some(added_after));
+        let response = self.request(&url)?;
+        let manifest: Manifest = self.parse_json(response)?;
+        Ok(manifest.objects.is_some_and(|records| !records.is_empty()))
+    }
+
+    /// Lists the ids of the objects in a collection that are absent from `local_ids`.
+    ///
+    /// Only the collection's manifest is read, so no objects are downloaded. The result can
+    /// be passed to [`get_cc_indicators_by_ids`](Self::get_cc_indicators_by_ids) to fetch just
+    /// what is missing.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `local_ids`: The STIX ids already held locally.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Vec<String>)` with each missing id once, in manifest order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let missing = agent.missing_locally(None, false, &local_ids)?;
+    /// let ids: Vec<&str> = missing.iter().map(String::as_str).collect();
+    /// let indicators = agent.get_cc_indicators_by_ids(None, false, &ids)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn missing_locally(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        local_ids: &HashSet<String>,
+    ) -> Result<Vec<String>> {
+        let root = self.root(private)?;
+        let collection = self.resolve_collection(collection_id, root)?;
+        let mut seen = HashSet::new();
+        Ok(self
+            .manifest_records(root, &collection, None)?
+            .into_iter()
+            .map(|record| record.id)
+            .filter(|id| !local_ids.contains(id) && seen.insert(id.clone()))
+            .collect())
+    }
+
+    /// Retrieves the manifest of a collection, following every page.
+    ///
+    /// The manifest lists the id, `date_added`, version, and media type of each object without
+    /// its STIX body, which makes it a cheap way to see what changed before downloading
+    /// objects.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `added_after`: Only records of objects added after this timestamp are listed, if
+    ///   given.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let records = agent.get_manifest(None, false, Some("2024-01-01T00:00:00Z"))?;
+    /// for record in &records {
+    ///     println!("{} {}", record.id, record.version);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_manifest(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        added_after: Option<&str>,
+    ) -> Result<Vec<ManifestRecord>> {
+        let added_after = self.added_after_or_default(added_after);
+        validate_added_after(added_after)?;
+        let root = self.root(private)?;
+        let collection = self.resolve_collection(collection_id, root)?;
+        self.manifest_records(root, &collection, added_after)
+    }
+
+    /// Estimates the number of objects in a collection from its manifest, without fetching
+    /// any object bodies.
+    ///
+    /// The manifest is requested in pages of 10,000 records, and at most 3 pages are read.
+    /// For a collection of up to 30,000 records the count is therefore exact; for a larger
+    /// one it is capped at the records read, so it only tells the caller that the collection
+    /// is at least that big. Each version of an object counts as a record.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// if agent.estimate_collection_size(None, false)? > 10_000 {
+    ///     // Pull in batches instead
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_manifest`.
+    pub fn estimate_collection_size(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+    ) -> Result<usize> {
+        const PAGE_SIZE: usize = 10_000;
+        const MAX_PAGES: usize = 3;
+        let root = self.root(private)?;
+        let collection = self.resolve_collection(collection_id, root)?;
+        let url = self.manifest_url(root, &collection, PAGE_SIZE, None);
+        let (mut count, mut pages) = (0, 0);
+        self.fetch_pages(&url, true, |page: Manifest| {
+            count += page.objects.map_or(0, |records| records.len());
+            pages += 1;
+            Ok(pages < MAX_PAGES)
+        })?;
+        Ok(count)
+    }
+
+    /// Reads every page of a collection's manifest.
+    fn manifest_records(
+        &self,
+        root: &str,
+        collection: &str,
+        added_after: Option<&str>,
+    ) -> Result<Vec<ManifestRecord>> {
+        let url = self.manifest_url(root, collection, 1000, added_after);
+        let mut records: Vec<ManifestRecord> = Vec::new();
+        self.fetch_pages(&url, true, |page: Manifest| {
+            records.extend(page.objects.unwrap_or_default());
+            Ok(true)
+        })?;
+        Ok(records)
+    }
+
+    /// Enables or disables deduplication of the indicators returned by `get_cc_indicators`.
+    ///
+    /// A collection may hold several versions of the same indicator, which can be spread
+    /// across pages. When enabled, only the version with the latest `modified` timestamp of
+    /// each `id` is kept, at the position where the `id` was first seen. Deduplication is
+    /// disabled by default, so every version is returned.
+    ///
+    /// # Parameters
+    ///
+    /// - `dedup`: Whether to keep only the latest version of each indicator.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key").with_dedup(true);
+    /// ```
+    #[must_use]
+    pub const fn with_dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Enables or disables verification of response bodies against the server's `Content-Digest`.
+    ///
+    /// When enabled, each response body is fully read and hashed before it is parsed. If the
+    /// server advertises a `sha-256` or `sha-512` digest that does not match the received body,
+    /// the request fails with `IntegrityError`. Responses without a supported digest are parsed
+    /// as usual. Verification is disabled by default.
+    ///
+    /// # Parameters
+    ///
+    /// - `verify`: Whether to verify response digests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key").with_verify_digest(true);
+    /// ```
+    #[must_use]
+    pub const fn with_verify_digest(mut self, verify: bool) -> Self {
+        self.verify_digest = verify;
+        self
+    }
+
+    /// Sets the path template of the collections endpoint.
+    ///
+    /// Servers that do not follow the standard TAXII 2.1 layout can be supported by changing
+    /// where collections are listed. The `{root}` placeholder is replaced by the API root.
+    /// Defaults to `{root}/collections/`.
+    ///
+    /// # Parameters
+    ///
+    /// - `template`: The path template, relative to the base URL.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_collections_path("taxii/{root}/feeds/");
+    /// ```
+    #[must_use]
+    pub fn with_collections_path(mut self, template: &str) -> Self {
+        self.collections_path = template.to_string();
+        self
+    }
+
+    /// Sets the path template of the objects endpoint of a collection.
+    ///
+    /// The `{root}` placeholder is replaced by the API root and `{collection}` by the
+    /// collection ID. Defaults to `{root}/collections/{collection}/objects/`.
+    ///
+    /// # Parameters
+    ///
+    /// - `template`: The path template, relative to the base URL.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+    ///     .with_objects_path("taxii/{root}/feeds/{collection}/items/");
+    /// ```
+    #[must_use]
+    pub fn with_objects_path(mut self, template: &str) -> Self {
+        self.objects_path = template.to_string();
+        self
+    }
+
+    /// Returns the headers sent with every request, with credentials redacted.
+    ///
+    /// The value of the `Authorization` header is replaced by `[REDACTED]`, so the result is
+    /// safe to log or assert on in tests.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// for (name, value) in agent.common_headers() {
+    ///     println!("{name}: {value}");
+    /// }
+    /// ```
+    #[must_use]
+    pub fn common_headers(&self) -> Vec<(&str, &str)> {
+        self.common_headers
+            .iter()
+            .map(|(key, value)| {
+                if key.eq_ignore_ascii_case("Authorization") {
+                    (key.as_str(), "[REDACTED]")
+                } else {
+                    (key.as_str(), value.as_str())
+                }
+            })
+            .collect()
+    }
+
+    /// Renders the objects endpoint path of a collection.
+    fn objects_endpoint(&self, root: &str, collection: &str) -> String {
+        self.objects_path
+            .replace(ROOT_PLACEHOLDER, root)
+            .replace(COLLECTION_PLACEHOLDER, collection)
+    }
+
+    /// Returns `true` if `url` points below the private account root.
+    fn is_private_path(&self, url: &str) -> bool {
+        let prefix = self
+            .collections_path
+            .split("{root}")
+            .next()
+            .unwrap_or_default();
+        url.starts_with(&format!("{prefix}{}/", self.account))
+    }
+
+    /// Deserializes a response body, verifying its `Content-Digest` first if enabled.
+    fn parse_json<T: DeserializeOwned>(&self, response: Response) -> Result<T> {
+        let result = self.read_json(response);
+        if let Err(e) = &result {
+            self.metrics.on_error(e);
+        }
+        result
+    }
+
+    /// Deserializes a response body as `parse_json` does, after checking that its
+    /// `Content-Type`, if sent, is a JSON media type such as `application/taxii+json`.
+    fn parse_taxii_json<T: DeserializeOwned>(&self, response: Response) -> Result<T> {
+        let content_type = response
+            .header("Content-Type")
+            .unwrap_or("application/json");
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let is_json = media_type
+            .strip_prefix("application/")
+            .is_some_and(|subtype| subtype == "json" || subtype.ends_with("+json"));
+        if !is_json {
+            let error = Box::new(UnexpectedContentType {
+                got: content_type.to_string(),
+            });
+            self.metrics.on_error(&error);
+            return Err(error);
+        }
+        self.parse_json(response)
+    }
+
+    /// Reads and deserializes a response body, reporting the bytes read to the metrics sink.
+    ///
+    /// The body is deserialized straight from the response stream rather than buffered
+    /// first, so peak memory stays close to the size of the parsed value even for very large
+    /// objects. When the `Content-Digest` is checked, the body is hashed while it streams and
+    /// the digest is verified once the body has been read to its end. A digest mismatch takes
+    /// precedence over a deserialization error.
+    fn read_json<T: DeserializeOwned>(&self, response: Response) -> Result<T> {
+        let digest = if self.verify_digest {
+            response.header("Content-Digest").map(str::to_string)
+        } else {
+            None
+        };
+        let mut reader = BodyReader::new(response.into_reader(), digest.is_some());
+        let result = serde_json::from_reader(&mut reader);
+        if let Some(digest) = digest {
+            std::io::copy(&mut reader, &mut std::io::sink())
+                .map_err(|e| TaxiiConnectionError(e.to_string()))?;
+            self.metrics.on_bytes(reader.count);
+            reader.verify(&digest)?;
+        } else {
+            self.metrics.on_bytes(reader.count);
+        }
+        result.map_err(|e| Box::new(JsonDeserializationError(e.to_string())))
+    }
+
+    /// Retrieves a list of cyber threat indicators from the `CloudCover` TAXII server.
+    ///
+    /// This method fetches cyber threat indicators from a specified collection. It supports
+    /// filtering based on a timestamp, custom matches, and can optionally follow pagination
+    /// to retrieve all available indicators.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional reference to a string representing the collection ID
+    ///   from which to retrieve indicators. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `limit`: An optional usize value representing the maximum number of indicators to
+    ///   retrieve in a single request. Defaults to 1000 if `None`.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `added_after`: An optional reference to a string representing a timestamp. If provided,
+    ///   only indicators added after this timestamp will be retrieved.
+    ///
+    /// - `matches`: A reference to an optional `HashMap` with filter criteria in the form
+    ///   of key-value pairs. The keys and values are references to strings. Filters are added
+    ///   to the query sorted by key, so the same filters always produce the same URL.
+    ///
+    /// - `follow_pages`: A boolean flag indicating whether to follow pagination links to retrieve
+    ///   additional indicators beyond the initial request (`true`), or to only retrieve the indicators
+    ///   from the initial request (`false`).
+    ///
+    /// # Returns
+    ///
+    /// Returns a `Result<Vec<CCIndicator>>` which is either:
+    /// - `Ok(Vec<CCIndicator>)` containing the list of retrieved indicators.
+    /// - `Err(Box<TaxiiError>)` indicating an error occurred during the retrieval process.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// mut matches = std::Collections::HashMap::new();
+    /// matches.insert("type", "indicator");
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let indicators_result = agent.get_cc_indicators(
+    ///     Some("collection_id"),
+    ///     Some(500),
+    ///     true,
+    ///     Some("2024-01-01T00:00:00Z"),
+    ///     &Some(matches),
+    ///     true
+    /// );
+    ///
+    /// match indicators_result {
+    ///     Ok(indicators) => {
+    ///         // Process the list of indicators
+    ///     }
+    ///     Err(error) => {
+    ///         // Handle the error
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// This method can return various error types encapsulated within `TaxiiError`, such as:
+    /// - `TaxiiCollectionError` if no collection is available or specified collection ID is invalid.
+    /// - `InvalidFilter` if `added_after` is not an RFC 3339 timestamp, or `match[version]` or
+    ///   `match[spec_version]` has a value the TAXII specification does not define. See
+    ///   [`VersionFilter`] and [`taxii_timestamp`].
+    /// - `JsonDeserializationError` if there is an error in parsing the response from the server.
+    /// - Other errors related to network connectivity or server responses.
+    pub fn get_cc_indicators(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+        follow_pages: bool,
+    ) -> Result<Vec<CCIndicator>> {
+        let url = self.objects_url(
+            collection_id,
+            limit,
+            private,
+            self.added_after_or_default(added_after),
+            matches.as_ref(),
+        )?;
+        let mut all_indicators: Vec<CCIndicator> = Vec::new();
+        let mut positions: HashMap<String, usize> = HashMap::new();
+        self.fetch_pages(&url, follow_pages, |page: CCEnvelope| {
+            if !self.dedup {
+                all_indicators.extend(page.objects);
+                return Ok(true);
+            }
+            for indicator in page.objects {
+                if let Some(&position) = positions.get(&indicator.id) {
+                    let existing = &mut all_indicators[position];
+                    if compare_timestamps(&indicator.modified, &existing.modified)
+                        == Ordering::Greater
+                    {
+                        *existing = indicator;
+                    }
+                } else {
+                    positions.insert(indicator.id.clone(), all_indicators.len());
+                    all_indicators.push(indicator);
+                }
+            }
+            Ok(true)
+        })?;
+        Ok(all_indicators)
+    }
+
+    /// Retrieves cyber threat indicators added after a point in time.
+    ///
+    /// This is `get_cc_indicators` with `added_after` given as a `DateTime<Utc>`, so callers
+    /// do not have to format the timestamp themselves. It is sent as formatted by
+    /// [`taxii_timestamp`].
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`, `limit`, `private`, `matches`, `follow_pages`: As for
+    ///   [`get_cc_indicators`](Self::get_cc_indicators).
+    ///
+    /// - `since`: Only indicators added after this point in time are retrieved.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let since = DateTime::<Utc>::from(SystemTime::now() - Duration::from_secs(86_400));
+    /// let indicators = agent.get_cc_indicators_since(None, None, false, since, &None, true)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    #[cfg(feature = "chrono")]
+    pub fn get_cc_indicators_since(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        since: DateTime<Utc>,
+        matches: &Option<HashMap<&str, &str>>,
+        follow_pages: bool,
+    ) -> Result<Vec<CCIndicator>> {
+        self.get_cc_indicators(
+            collection_id,
+            limit,
+            private,
+            Some(&taxii_timestamp(since)),
+            matches,
+            follow_pages,
+        )
+    }
+
+    /// Retrieves a single page of cyber threat indicators, keeping its pagination metadata.
+    ///
+    /// This is the building block of `get_cc_indicators` for callers that drive pagination
+    /// themselves, such as a resumable sync that persists the cursor between runs. Pass the
+    /// `next` cursor of one page to request the following one, while `more` is `true`.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`, `limit`, `private`, `added_after`, `matches`: As for
+    ///   [`get_cc_indicators`](Self::get_cc_indicators). Send the same values for every page.
+    ///
+    /// - `next`: The cursor returned by the previous page, or `None` for the first page.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let mut cursor = load_cursor();
+    /// loop {
+    ///     let page = agent.get_cc_indicators_page(None, None, false, None, &None, cursor.as_deref())?;
+    ///     store(page.objects());
+    ///     cursor = page.next().filter(|_| page.more()).map(str::to_string);
+    ///     save_cursor(&cursor);
+    ///     if cursor.is_none() {
+    ///         break;
+    ///     }
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_cc_indicators_page(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+        next: Option<&str>,
+    ) -> Result<CCEnvelope> {
+        let url = self.objects_url(
+            collection_id,
+            limit,
+            private,
+            self.added_after_or_default(added_after),
+            matches.as_ref(),
+        )?;
+        self.fetch_page(&format!("{url}{}", next.map_or(String::new(), next_query)))
+    }
+
+    /// Retrieves up to `max_results` cyber threat indicators in pages of `page_size`.
+    ///
+    /// Unlike `get_cc_indicators`, where `limit` is only the page size, this keeps following
+    /// pages until `max_results` indicators are collected or the server has no more, so a
+    /// large total can be pulled in modest pages. The page size is lowered to `max_results`
+    /// when that is smaller, and no request is made when `max_results` is 0.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `page_size`: The number of indicators requested per page, sent as `limit`.
+    ///
+    /// - `max_results`: The maximum number of indicators returned in total.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `added_after` and `matches`: Filters applied as for
+    ///   [`get_cc_indicators`](Self::get_cc_indicators).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let indicators = agent.get_cc_indicators_capped(None, 500, 10_000, false, None, &None)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_cc_indicators_capped(
+        &self,
+        collection_id: Option<&str>,
+        page_size: usize,
+        max_results: usize,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+    ) -> Result<Vec<CCIndicator>> {
+        if max_results == 0 {
+            return Ok(Vec::new());
+        }
+        let limit = Some(page_size.min(max_results));
+        let url = self.objects_url(
+            collection_id,
+            limit,
+            private,
+            self.added_after_or_default(added_after),
+            matches.as_ref(),
+        )?;
+        let mut indicators: Vec<CCIndicator> = Vec::new();
+        self.fetch_pages(&url, true, |page: CCEnvelope| {
+            indicators.extend(page.objects);
+            indicators.truncate(max_results);
+            Ok(indicators.len() < max_results)
+        })?;
+        Ok(indicators)
+    }
+
+    /// Retrieves the raw objects of several STIX types from a collection in a single pull.
+    ///
+    /// The types are sent as one comma-separated `match[type]` filter, each type URL-encoded
+    /// on its own, so a single request covers them all instead of one pull per type. Every page is followed
+    /// and the objects are returned as raw JSON, since they need not be indicators.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `types`: The STIX types to retrieve (e.g., `indicator`, `malware`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let objects = agent.get_cc_objects_of_types(None, false, &["indicator", "malware"])?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_cc_objects_of_types(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        types: &[&str],
+    ) -> Result<Vec<serde_json::Value>> {
+        let joined = types
+            .iter()
+            .map(|r#type| utf8_percent_encode(r#type, MATCH_VALUE).to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let matches = HashMap::from([("type", joined.as_str())]);
+        let url = self.objects_url(collection_id, None, private, None, Some(&matches))?;
+        let mut objects: Vec<serde_json::Value> = Vec::new();
+        self.fetch_pages(&url, true, |mut page: serde_json::Value| {
+            if let Some(serde_json::Value::Array(page_objects)) = page.get_mut("objects") {
+                objects.append(page_objects);
+            }
+            Ok(true)
+        })?;
+        Ok(objects)
+    }
+
+    /// Downloads every object of a collection into a STIX bundle file at `path`.
+    ///
+    /// The bundle gets a fresh random id. Objects are written to the file page by page as
+    /// they arrive, so memory use stays bounded by the size of a single page. An existing file
+    /// at `path` is overwritten.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `path`: Where to write the bundle.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(usize)` with the number of objects written.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let count = agent.dump_collection_bundle(None, false, Path::new("feed.json"))?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `IoError` if the file cannot be created or written.
+    /// - Returns the same errors as `get_cc_indicators` for the requests. The file is left
+    ///   incomplete in that case.
+    pub fn dump_collection_bundle(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        path: &Path,
+    ) -> Result<usize> {
+        let url = self.objects_url(collection_id, None, private, None, None)?;
+        let mut file = BufWriter::new(File::create(path).map_err(IoError)?);
+        write!(
+            file,
+            r#"{{"type":"bundle","id":"bundle--{}","objects":["#,
+            uuid::Uuid::new_v4()
+        )
+        .map_err(IoError)?;
+        let mut count = 0;
+        self.fetch_pages(&url, true, |page: serde_json::Value| {
+            let objects = page.get("objects").and_then(serde_json::Value::as_array);
+            for object in objects.into_iter().flatten() {
+                if count > 0 {
+                    file.write_all(b",").map_err(IoError)?;
+                }
+                serde_json::to_writer(&mut file, object).map_err(|e| IoError(e.into()))?;
+                count += 1;
+            }
+            Ok(true)
+        })?;
+        file.write_all(b"]}").map_err(IoError)?;
+        file.flush().map_err(IoError)?;
+        Ok(count)
+    }
+
+    /// Writes the cyber threat indicators of a collection to `writer` as CSV, following every
+    /// page.
+    ///
+    /// A header row is written first, then one row per indicator as each page arrives, so
+    /// memory use stays bounded by the size of a single page however large the pull. The
+    /// columns are `id`, `type`, `name`, `pattern`, `pattern_type`, `pattern_version`,
+    /// `spec_version`, `created`, `modified`, `valid_from`, `confidence`, `description`, and
+    /// `labels`, with the labels joined by `;`. Absent optional values are left empty. Fields
+    /// containing commas, quotes, or line breaks are quoted as described in RFC 4180. Wrap
+    /// unbuffered writers, such as a `File`, in a `BufWriter`.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`, `private`, `added_after`, `matches`: As for
+    ///   [`get_cc_indicators`](Self::get_cc_indicators).
+    ///
+    /// - `writer`: Where to write the CSV.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(usize)` with the number of rows written, not counting the header.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let file = BufWriter::new(File::create("indicators.csv")?);
+    /// let rows = agent.stream_cc_indicators_csv(None, false, None, &None, file)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `IoError` if writing fails.
+    /// - Returns the same errors as `get_cc_indicators` for the requests. The output is left
+    ///   incomplete in that case.
+    pub fn stream_cc_indicators_csv(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+        mut writer: impl Write,
+    ) -> Result<usize> {
+        let url = self.objects_url(
+            collection_id,
+            None,
+            private,
+            self.added_after_or_default(added_after),
+            matches.as_ref(),
+        )?;
+        writer.write_all(CSV_HEADER.as_bytes()).map_err(IoError)?;
+        let mut count = 0;
+        self.fetch_pages(&url, true, |page: CCEnvelope| {
+            for indicator in page {
+                writer
+                    .write_all(csv_row(&indicator).as_bytes())
+                    .map_err(IoError)?;
+                count += 1;
+            }
+            Ok(true)
+        })?;
+        writer.flush().map_err(IoError)?;
+        Ok(count)
+    }
+
+    /// Downloads every object of a collection into one NDJSON file per STIX type in `dir`.
+    ///
+    /// Each object is written as a single line to `<type>.ndjson` (e.g., `indicator.ndjson`,
+    /// `malware.ndjson`) as pages arrive. Existing files with those names are overwritten.
+    /// The directory must already exist.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `dir`: The directory to write the files to.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(HashMap<String, usize>)` with the number of objects written per type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let counts = agent.dump_collection_by_type(None, false, Path::new("dump"))?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `IoError` if a file cannot be created or written.
+    /// - Returns `JsonDeserializationError` if an object has no valid STIX `type`, since it
+    ///   cannot be safely used as a file name.
+    /// - Returns the same errors as `get_cc_indicators` for the requests. Files may be left
+    ///   incomplete in these cases.
+    pub fn dump_collection_by_type(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        dir: &Path,
+    ) -> Result<HashMap<String, usize>> {
+        let url = self.objects_url(collection_id, None, private, None, None)?;
+        let mut files: HashMap<String, BufWriter<File>> = HashMap::new();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        self.fetch_pages(&url, true, |page: serde_json::Value| {
+            let objects = page.get("objects").and_then(serde_json::Value::as_array);
+            for object in objects.into_iter().flatten() {
+                let object_type = object["type"]
+                    .as_str()
+                    .filter(|object_type| is_stix_type(object_type))
+                    .ok_or_else(|| {
+                        JsonDeserializationError(format!("Object has no valid STIX type: {object}"))
+                    })?;
+                if !files.contains_key(object_type) {
+                    let file =
+                        File::create(dir.join(format!("{object_type}.ndjson"))).map_err(IoError)?;
+                    files.insert(object_type.to_string(), BufWriter::new(file));
+                }
+                if let Some(file) = files.get_mut(object_type) {
+                    serde_json::to_writer(&mut *file, object).map_err(|e| IoError(e.into()))?;
+                    file.write_all(b"\n").map_err(IoError)?;
+                }
+                *counts.entry(object_type.to_string()).or_default() += 1;
+            }
+            Ok(true)
+        })?;
+        for file in files.values_mut() {
+            file.flush().map_err(IoError)?;
+        }
+        Ok(counts)
+    }
+
+    /// Retrieves every object of a collection as raw JSON, grouped by STIX type.
+    ///
+    /// All pages are followed, and objects keep their server order within each type, which
+    /// gives a complete snapshot of the collection organized by type. The whole collection is
+    /// held in memory; use [`dump_collection_by_type`](Self::dump_collection_by_type) for
+    /// collections too large for that.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let snapshot = agent.snapshot_collection(None, false)?;
+    /// let malware = snapshot.get("malware").map_or(0, Vec::len);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `JsonDeserializationError` if an object has no STIX `type`.
+    /// - Returns the same errors as `get_cc_indicators` for the requests.
+    pub fn snapshot_collection(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+    ) -> Result<HashMap<String, Vec<serde_json::Value>>> {
+        let url = self.objects_url(collection_id, None, private, None, None)?;
+        let mut snapshot: HashMap<String, Vec<serde_json::Value>> = HashMap::new();
+        self.fetch_pages(&url, true, |mut page: serde_json::Value| {
+            let objects = match page["objects"].take() {
+                serde_json::Value::Array(objects) => objects,
+                _ => Vec::new(),
+            };
+            for object in objects {
+                let object_type = object["type"]
+                    .as_str()
+                    .ok_or_else(|| {
+                        JsonDeserializationError(format!("Object has no STIX type: {object}"))
+                    })?
+                    .to_string();
+                snapshot.entry(object_type).or_default().push(object);
+            }
+            Ok(true)
+        })?;
+        Ok(snapshot)
+    }
+
+    /// Retrieves the distinct malicious IP addresses of the default collection.
+    ///
+    /// Every page of indicators in the root's
+    /// [default collection](CCTaxiiClient#default-collection) is retrieved, and the indicators
+    /// whose STIX pattern is a single `ipv4-addr:value` or `ipv6-addr:value` equality
+    /// comparison are parsed into an `IpAddr`. CIDR ranges (e.g., `198.51.100.0/24`),
+    /// negated comparisons, and compound patterns do not name a single address and are skipped.
+    ///
+    /// # Parameters
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Vec<IpAddr>)` with each address once, in the order first seen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let ips = agent.get_default_malicious_ips(false)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_default_malicious_ips(&self, private: bool) -> Result<Vec<IpAddr>> {
+        let indicators = self.get_cc_indicators(None, None, private, None, &None, true)?;
+        let mut seen = HashSet::new();
+        Ok(indicators
+            .iter()
+            .filter(|indicator| indicator.pattern_type == "stix")
+            .filter_map(|indicator| parse_comparison(&indicator.pattern))
+            .filter(|comparison| {
+                matches!(
+                    comparison.object_path.as_str(),
+                    "ipv4-addr:value" | "ipv6-addr:value"
+                ) && comparison.operator == "="
+                    && !comparison.negated
+            })
+            .filter_map(|comparison| comparison.value.parse::<IpAddr>().ok())
+            .filter(|ip| seen.insert(*ip))
+            .collect())
+    }
+
+    /// Retrieves cyber threat indicators from several collections, tagging each with its source.
+    ///
+    /// The sources are read in order and their indicators merged into one list. Each indicator
+    /// is paired with the `SourceInfo` it was read from, so merged results can still be
+    /// deduplicated and attributed per source. Private roots use the private credentials if set.
+    ///
+    /// # Parameters
+    ///
+    /// - `sources`: The API roots and collections to read from.
+    ///
+    /// - `added_after`: An optional reference to a string representing a timestamp. If provided,
+    ///   only indicators added after this timestamp will be retrieved.
+    ///
+    /// - `matches`: A reference to an optional `HashMap` with filter criteria in the form
+    ///   of key-value pairs, applied to every source.
+    ///
+    /// - `follow_pages`: A boolean flag indicating whether to follow pagination links of each
+    ///   source (`true`), or to only retrieve its first page (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let sources = [
+    ///     SourceInfo { root: "api".to_string(), collection_id: "public_id".to_string() },
+    ///     SourceInfo { root: "my_username".to_string(), collection_id: "private_id".to_string() },
+    /// ];
+    /// for (source, indicator) in agent.get_cc_indicators_from_sources(&sources, None, &None, true)? {
+    ///     println!("{} from {}/{}", indicator.id, source.root, source.collection_id);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`, stopping at the first failing source.
+    pub fn get_cc_indicators_from_sources(
+        &self,
+        sources: &[SourceInfo],
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+        follow_pages: bool,
+    ) -> Result<Vec<(SourceInfo, CCIndicator)>> {
+        let mut tagged: Vec<(SourceInfo, CCIndicator)> = Vec::new();
+        for source in sources {
+            let url = self.collection_objects_url(
+                &source.root,
+                &source.collection_id,
+                None,
+                self.added_after_or_default(added_after),
+                matches.as_ref(),
+            );
+            self.fetch_pages(&url, follow_pages, |page: CCEnvelope| {
+                tagged.extend(
+                    page.objects
+                        .into_iter()
+                        .map(|indicator| (source.clone(), indicator)),
+                );
+                Ok(true)
+            })?;
+        }
+        Ok(tagged)
+    }
+
+    /// Retrieves cyber threat indicators along with the raw JSON of every page received.
+    ///
+    /// This behaves like [`get_cc_indicators`](Self::get_cc_indicators), but also returns each
+    /// page envelope exactly as the server sent it, so the untouched responses can be archived
+    /// for auditing.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`, `limit`, `private`, `added_after`, `matches`, `follow_pages`: As for
+    ///   `get_cc_indicators`.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok((indicators, pages))` with the parsed indicators and one raw JSON value per
+    /// page, in the order they were received.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let (indicators, pages) =
+    ///     agent.get_cc_indicators_with_raw(None, Some(500), false, None, &None, true)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_cc_indicators_with_raw(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+        follow_pages: bool,
+    ) -> Result<(Vec<CCIndicator>, Vec<serde_json::Value>)> {
+        let url = self.objects_url(
+            collection_id,
+            limit,
+            private,
+            self.added_after_or_default(added_after),
+            matches.as_ref(),
+        )?;
+        let mut all_indicators: Vec<CCIndicator> = Vec::new();
+        let mut pages: Vec<serde_json::Value> = Vec::new();
+        self.fetch_pages(&url, follow_pages, |page: serde_json::Value| {
+            let envelope: CCEnvelope = serde_json::from_value(page.clone())
+                .map_err(|e| JsonDeserializationError(e.to_string()))?;
+            all_indicators.extend(envelope.objects);
+            pages.push(page);
+            Ok(true)
+        })?;
+        Ok((all_indicators, pages))
+    }
+
+    /// Retrieves cyber threat indicators from every page as a map keyed by STIX id.
+    ///
+    /// When several versions of the same object are returned, only the one with the most
+    /// recent `modified` timestamp is kept.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`, `limit`, `private`, `added_after`, `matches`: As for
+    ///   [`get_cc_indicators`](Self::get_cc_indicators). All pages are followed.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let indicators = agent.get_cc_indicators_map(None, None, false, None, &None)?;
+    /// let indicator = indicators.get("indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_cc_indicators_map(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+    ) -> Result<HashMap<String, CCIndicator>> {
+        let url = self.objects_url(
+            collection_id,
+            limit,
+            private,
+            self.added_after_or_default(added_after),
+            matches.as_ref(),
+        )?;
+        let mut indicators: HashMap<String, CCIndicator> = HashMap::new();
+        self.fetch_pages(&url, true, |page: CCEnvelope| {
+            for indicator in page.objects {
+                let newer = indicators.get(&indicator.id).map_or(true, |existing| {
+                    compare_timestamps(&indicator.modified, &existing.modified) == Ordering::Greater
+                });
+                if newer {
+                    indicators.insert(indicator.id.clone(), indicator);
+                }
+            }
+            Ok(true)
+        })?;
+        Ok(indicators)
+    }
+
+    /// Retrieves the indicators of a collection grouped by their `pattern_type`.
+    ///
+    /// Feeds mix `stix`, `sigma`, `yara`, and `snort` patterns, so grouping them lets each
+    /// group be routed to the tool that understands it. Every page is followed and the
+    /// indicators keep their server order within each group.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let grouped = agent.get_cc_indicators_by_pattern_type(None, false)?;
+    /// let sigma = grouped.get("sigma");
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_cc_indicators_by_pattern_type(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+    ) -> Result<HashMap<String, Vec<CCIndicator>>> {
+        let url = self.objects_url(collection_id, None, private, None, None)?;
+        let mut grouped: HashMap<String, Vec<CCIndicator>> = HashMap::new();
+        self.fetch_pages(&url, true, |page: CCEnvelope| {
+            for indicator in page.objects {
+                grouped
+                    .entry(indicator.pattern_type.clone())
+                    .or_default()
+                    .push(indicator);
+            }
+            Ok(true)
+        })?;
+        Ok(grouped)
+    }
+
+    /// Retrieves the `n` most recently created indicators of a collection, newest first.
+    ///
+    /// TAXII servers return objects oldest first by `date_added`, which does not bound
+    /// `created`, so every page has to be read before the newest `n` are known. Only the
+    /// newest `n` seen so far are kept between pages, so memory stays bounded by `n` and the
+    /// page size. No request is made when `n` is 0.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `n`: The maximum number of indicators to return.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let latest = agent.get_newest_cc_indicators(None, false, 10)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_newest_cc_indicators(
+        &self,
+        collection_id: Option<&str>,
+        private: bool,
+        n: usize,
+    ) -> Result<Vec<CCIndicator>> {
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let url = self.objects_url(collection_id, None, private, None, None)?;
+        let mut newest: Vec<CCIndicator> = Vec::new();
+        self.fetch_pages(&url, true, |page: CCEnvelope| {
+            newest.extend(page.objects);
+            newest.sort_by(|a, b| compare_timestamps(&b.created, &a.created));
+            newest.truncate(n);
+            Ok(true)
+        })?;
+        Ok(newest)
+    }
+
+    /// Pulls a collection and merges it into an existing store of indicators keyed by STIX id.
+    ///
+    /// Every page is retrieved. A pulled indicator is inserted if its id is new to the store,
+    /// replaces the stored one if its `modified` timestamp is more recent, and is ignored
+    /// otherwise. This keeps a live cache current without rebuilding it.
+    ///
+    /// # Parameters
+    ///
+    /// - `store`: The indicators to merge into, keyed by STIX id.
+    ///
+    /// - `collection_id`: An optional collection ID. If `None`, the root's
+    ///   [default collection](CCTaxiiClient#default-collection) is used.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `added_after`: An optional reference to a string representing a timestamp. If provided,
+    ///   only indicators added after this timestamp will be retrieved.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(MergeReport)` counting the inserted, updated, and unchanged indicators.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let mut store = agent.get_cc_indicators_map(None, None, false, None, &None)?;
+    /// let report = agent.merge_into(&mut store, None, false, Some("2024-01-01T00:00:00Z"))?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`. Pages merged before the error remain
+    /// in the store.
+    pub fn merge_into(
+        &self,
+        store: &mut HashMap<String, CCIndicator>,
+        collection_id: Option<&str>,
+        private: bool,
+        added_after: Option<&str>,
+    ) -> Result<MergeReport> {
+        let url = self.objects_url(
+            collection_id,
+            None,
+            private,
+            self.added_after_or_default(added_after),
+            None,
+        )?;
+        let mut report = MergeReport::default();
+        self.fetch_pages(&url, true, |page: CCEnvelope| {
+            for indicator in page.objects {
+                match store.get(&indicator.id) {
+                    None => report.inserted += 1,
+                    Some(existing)
+                        if compare_timestamps(&indicator.modified, &existing.modified)
+                            == Ordering::Greater =>
+                    {
+                        report.updated += 1;
+                    }
+                    Some(_) => {
+                        report.unchanged += 1;
+                        continue;
+                    }
+                }
+                store.insert(indicator.id.clone(), indicator);
+            }
+            Ok(true)
+        })?;
+        Ok(report)
+    }
+
+    /// Retrieves the information of an API root, such as the largest request body it accepts.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The API root, with or without surrounding slashes (e.g., `api` or `/api/`,
+    ///   as listed in the discovery `api_roots`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let api_root = agent.get_api_root("api")?;
+    /// println!("Requests may be up to {} bytes", api_root.max_content_length);
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `TaxiiNotFound` if the API root does not exist.
+    /// - Returns `JsonDeserializationError` if the response cannot be parsed.
+    /// - Other errors related to network connectivity or server responses.
+    pub fn get_api_root(&self, root: &str) -> Result<ApiRoot> {
+        let response = self.request(&format!("{}/", root.trim_matches('/')))?;
+        self.parse_json(response)
+    }
+
+    /// Adds STIX objects to a writable collection.
+    ///
+    /// The objects are sent in a TAXII envelope with a `POST` to the collection's objects
+    /// endpoint. The collection is looked up first, so a missing or read-only collection, or
+    /// one not accepting STIX 2.1 objects, is rejected before anything is sent. The server usually processes the objects in the
+    /// background; poll the returned status with [`wait_for_status`](Self::wait_for_status)
+    /// to learn the outcome.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`: The ID of the collection to add the objects to.
+    ///
+    /// - `objects`: The STIX objects to add.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let status = agent.add_objects("c1", &[indicator], true)?;
+    /// let status = agent.wait_for_status(&status.id, true, interval, timeout)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `TaxiiCollectionError` if the collection does not exist, is not writable, or
+    ///   does not support STIX 2.1.
+    /// - Returns `JsonDeserializationError` if the objects cannot be serialized or the status
+    ///   cannot be parsed.
+    /// - Other errors related to network connectivity or server responses.
+    pub fn add_objects(
+        &self,
+        collection_id: &str,
+        objects: &[serde_json::Value],
+        private: bool,
+    ) -> Result<Status> {
+        let root = self.root(private)?;
+        let collection = self
+            .get_collections_detailed(root)?
+            .into_iter()
+            .find(|collection| collection.id == collection_id)
+            .ok_or_else(|| {
+                TaxiiCollectionError(format!("Collection {collection_id} not found in {root}"))
+            })?;
+        if !collection.can_write {
+            return Err(Box::new(TaxiiCollectionError(format!(
+                "Collection {collection_id} is not writable"
+            ))));
+        }
+        if !collection.supports_media_type(STIX_MEDIA_TYPE) {
+            return Err(Box::new(TaxiiCollectionError(format!(
+                "Collection {collection_id} does not accept {STIX_MEDIA_TYPE}"
+            ))));
+        }
+        let body = serde_json::to_vec(&serde_json::json!({ "objects": objects }))
+            .map_err(|e| JsonDeserializationError(e.to_string()))?;
+        let url = self.objects_endpoint(root, collection_id);
+        let response = self.request_method("POST", &url, Some(&body))?;
+        self.parse_json(response)
+    }
+
+    /// Retrieves the status of a request that added objects to a collection.
+    ///
+    /// # Parameters
+    ///
+    /// - `status_id`: The ID of the status resource returned when the objects were added.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let status = agent.get_status("2d086da7-4bdc-4f91-900e-d77486753710", true)?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `TaxiiNotFound` if the server does not know the status ID.
+    /// - Returns `JsonDeserializationError` if the response cannot be parsed.
+    /// - Other errors related to network connectivity or server responses.
+    pub fn get_status(&self, status_id: &str, private: bool) -> Result<Status> {
+        self.get_status_at(self.root(private)?, status_id)
+    }
+
+    /// Retrieves the status of a request that added objects under any API root.
+    ///
+    /// `get_status` only covers the public and private roots. This reads the status resource
+    /// of `root` directly, for servers with other API roots.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The API root the objects were added under (e.g., `api`).
+    ///
+    /// - `status_id`: The ID of the status resource returned when the objects were added.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let status = agent.get_status_at("feeds", "2d086da7-4bdc-4f91-900e-d77486753710")?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_status`.
+    pub fn get_status_at(&self, root: &str, status_id: &str) -> Result<Status> {
+        let url = format!("{root}/status/{status_id}/");
+        let response = self.request(&url)?;
+        self.parse_json(response)
+    }
+
+    /// Polls the status of a request that added objects until it is `complete`.
+    ///
+    /// The status is fetched once right away and then every `poll_interval` until its
+    /// `status` is `complete`, or until `timeout` has elapsed since the call.
+    ///
+    /// # Parameters
+    ///
+    /// - `status_id`: The ID of the status resource returned when the objects were added.
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// - `poll_interval`: How long to wait between polls.
+    ///
+    /// - `timeout`: How long to keep polling before giving up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let status = agent.wait_for_status(
+    ///     "2d086da7-4bdc-4f91-900e-d77486753710",
+    ///     true,
+    ///     Duration::from_secs(2),
+    ///     Duration::from_secs(60),
+    /// )?;
+    /// let retry = status.failed_ids();
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Timeout` if the status is still not `complete` once `timeout` has elapsed.
+    /// - Returns the same errors as `get_status` if a poll fails.
+    pub fn wait_for_status(
+        &self,
+        status_id: &str,
+        private: bool,
+        poll_interval: Duration,
+        timeout: Duration,
+    ) -> Result<Status> {
+        self.poll_status_until_complete(self.root(private)?, status_id, poll_interval, timeout)
+    }
+
+    /// Polls the status of a request that added objects under any API root until it is
+    /// `complete`.
+    ///
+    /// This is `wait_for_status` for a root given by name rather than by the `private` flag.
+    /// The status is fetched once right away and then every `poll_interval` until its
+    /// `status` is `complete`, or until `max_wait` has elapsed since the call.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The API root the objects were added under (e.g., `api`).
+    ///
+    /// - `status_id`: The ID of the status resource returned when the objects were added.
+    ///
+    /// - `poll_interval`: How long to wait between polls.
+    ///
+    /// - `max_wait`: How long to keep polling before giving up.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let status = agent.poll_status_until_complete(
+    ///     "feeds",
+    ///     "2d086da7-4bdc-4f91-900e-d77486753710",
+    ///     Duration::from_secs(2),
+    ///     Duration::from_secs(60),
+    /// )?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// - Returns `Timeout` if the status is still not `complete` once `max_wait` has elapsed.
+    /// - Returns the same errors as `get_status_at` if a poll fails.
+    pub fn poll_status_until_complete(
+        &self,
+        root: &str,
+        status_id: &str,
+        poll_interval: Duration,
+        max_wait: Duration,
+    ) -> Result<Status> {
+        let started = Instant::now();
+        loop {
+            let status = self.get_status_at(root, status_id)?;
+            if status.status == "complete" {
+                return Ok(status);
+            }
+            let remaining = max_wait.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                return Err(Box::new(Timeout(format!(
+                    "Status {status_id} still {} after {max_wait:?}",
+                    status.status
+                ))));
+            }
+            thread::sleep(poll_interval.min(remaining));
+        }
+    }
+
+    /// Checks that the client can reach the server and read from the default collection.
+    ///
+    /// This runs three steps in order and records whether each succeeded and how long it took:
+    /// fetching the discovery information, listing the collections of the public or private
+    /// root, and fetching a single object from the
+    /// [default collection](CCTaxiiClient#default-collection). The object fetch is
+    /// skipped if listing the collections fails or returns no collections. Failures of these
+    /// steps are recorded in the report rather than returned as errors, so one report shows
+    /// how far the client got. Only a client that cannot run the test at all returns an error.
+    ///
+    /// # Parameters
+    ///
+    /// - `private`: A boolean flag indicating whether to use the private API root (`true`)
+    ///   or the public API root (`false`).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let report = agent.self_test(false)?;
+    /// for step in &report.steps {
+    ///     println!("{}: {} in {:?}", step.name, step.success, step.elapsed);
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns `TaxiiCollectionError` if `private` is set but the client has no private root.
+    pub fn self_test(&self, private: bool) -> Result<SelfTestReport> {
+        let root = self.root(private)?;
+        let mut report = SelfTestReport { steps: Vec::new() };
+        report.run("discovery", || self.get_discovery());
+        let collection = report.run("collections", || self.resolve_collection(None, root));
+        if let Some(collection) = collection {
+            report.run("objects", || {
+                self.get_cc_indicators(Some(&collection), Some(1), private, None, &None, false)
+            });
+        }
+        Ok(report)
+    }
+
+    /// Retrieves cyber threat indicators across pages until an overall deadline passes or the
+    /// pull is cancelled.
+    ///
+    /// Each request is still bound by the per-request timeout, but a long pull of a large
+    /// collection can exceed a job's total time budget. Once `deadline` has passed, no further
+    /// pages are requested and the indicators gathered so far are returned together with the
+    /// cursor of the next page, so the pull can be resumed later. The first page is always
+    /// requested unless the pull was cancelled before it started.
+    ///
+    /// The client's cancel token, if installed with
+    /// [`with_cancel_token`](Self::with_cancel_token), is checked before every page request, so
+    /// another thread can stop the pull by setting it. The indicators gathered so far are then
+    /// returned the same way, with `cancelled` set. This is the only paginated method that
+    /// keeps its data on cancellation.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`, `limit`, `private`, `added_after`, `matches`: As for
+    ///   [`get_cc_indicators`](Self::get_cc_indicators). Pages are followed until the
+    ///   collection is exhausted, the deadline passes, or the pull is cancelled.
+    ///
+    /// - `deadline`: An optional point in time after which no further pages are requested.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let cancel = Arc::new(AtomicBool::new(false));
+    /// let agent =
+    ///     CCTaxiiClient::new("my_username", "my_api_key").with_cancel_token(Arc::clone(&cancel));
+    /// let deadline = Instant::now() + Duration::from_secs(600);
+    /// let pull = agent.get_cc_indicators_partial(None, None, false, None, &None, Some(deadline))?;
+    /// if pull.timed_out || pull.cancelled {
+    ///     // Persist `pull.next` to resume later
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`.
+    pub fn get_cc_indicators_partial(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+        deadline: Option<Instant>,
+    ) -> Result<PartialIndicators> {
+        let mut pull = PartialIndicators {
+            indicators: Vec::new(),
+            next: None,
+            timed_out: false,
+            cancelled: self.is_cancelled(),
+        };
+        if pull.cancelled {
+            return Ok(pull);
+        }
+        let url = self.objects_url(
+            collection_id,
+            limit,
+            private,
+            self.added_after_or_default(added_after),
+            matches.as_ref(),
+        )?;
+        self.fetch_pages(&url, true, |page: CCEnvelope| {
+            let more = page.more.unwrap_or(false);
+            pull.indicators.extend(page.objects);
+            pull.next = page.next;
+            pull.timed_out = more && deadline.is_some_and(|deadline| Instant::now() >= deadline);
+            pull.cancelled = more && !pull.timed_out && self.is_cancelled();
+            Ok(!pull.timed_out && !pull.cancelled)
+        })?;
+        if !pull.timed_out && !pull.cancelled {
+            pull.next = None;
+        }
+        Ok(pull)
+    }
+
+    /// Retrieves cyber threat indicators in fixed-size batches, following every page.
+    ///
+    /// Indicators are buffered across page boundaries until `batch_size` of them have been
+    /// collected, at which point `flush` is called with the batch. Any remaining indicators are
+    /// flushed once the last page has been read, so the final batch may be smaller than
+    /// `batch_size`. This suits batched inserts into a database without holding the whole
+    /// collection in memory.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`, `limit`, `private`, `added_after`, `matches`: As for
+    ///   [`get_cc_indicators`](Self::get_cc_indicators). `limit` sets the page size.
+    ///
+    /// - `batch_size`: The number of indicators passed to each call of `flush`. A value of `0`
+    ///   is treated as `1`.
+    ///
+    /// - `flush`: A callback invoked with each batch. Returning an error stops the pull and
+    ///   propagates the error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// agent.batched_cc_indicators(None, Some(500), false, None, &None, 100, |batch| {
+    ///     // Insert the batch into a database
+    ///     Ok(())
+    /// })?;
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_cc_indicators`, as well as any error returned by `flush`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn batched_cc_indicators<F>(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+        batch_size: usize,
+        mut flush: F,
+    ) -> Result<()>
+    where
+        F: FnMut(&[CCIndicator]) -> Result<()>,
+    {
+        let batch_size = batch_size.max(1);
+        let url = self.objects_url(
+            collection_id,
+            limit,
+            private,
+            self.added_after_or_default(added_after),
+            matches.as_ref(),
+        )?;
+        let mut batch: Vec<CCIndicator> = Vec::with_capacity(batch_size);
+        self.fetch_pages(&url, true, |page: CCEnvelope| {
+            for indicator in page.objects {
+                batch.push(indicator);
+                if batch.len() == batch_size {
+                    flush(&batch)?;
+                    batch.clear();
+                }
+            }
+            Ok(true)
+        })?;
+        if !batch.is_empty() {
+            flush(&batch)?;
+        }
+        Ok(())
+    }
+
+    /// Iterates over the cyber threat indicators of a collection, following every page lazily.
+    ///
+    /// Only one page is held at a time: the next page is requested when the iterator is
+    /// advanced past the last indicator of the current one, so a collection can be processed
+    /// in bounded memory. A failed request yields a single `Err` item and ends the iteration,
+    /// as does failing to resolve the default collection.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`, `limit`, `private`, `added_after`, `matches`: As for
+    ///   [`get_cc_indicators`](Self::get_cc_indicators). `limit` sets the page size.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// for indicator in agent.iter_cc_indicators(None, Some(500), false, None, &None) {
+    ///     let indicator = indicator?;
+    ///     // Process and discard the indicator
+    /// }
+    /// ```
+    pub fn iter_cc_indicators(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+    ) -> impl Iterator<Item = Result<CCIndicator>> + '_ {
+        let (base, error) = match self.objects_url(
+            collection_id,
+            limit,
+            private,
+            self.added_after_or_default(added_after),
+            matches.as_ref(),
+        ) {
+            Ok(base) => (base, None),
+            Err(error) => (String::new(), Some(error)),
+        };
+        CCIndicatorIter {
+            client: self,
+            url: error.is_none().then(|| base.clone()),
+            base,
+            buffer: Vec::new().into_iter(),
+            error,
+        }
+    }
+
+    /// Iterates over the cyber threat indicators of a collection in approximate `valid_from`
+    /// order, following every page lazily.
+    ///
+    /// Indicators are pulled as by [`iter_cc_indicators`](Self::iter_cc_indicators), usually
+    /// with `added_after` set to the start of the time window being processed, and pass
+    /// through a heap holding at most `window` of them; the earliest is yielded each time the
+    /// heap overflows. The ordering is therefore per window, not strictly global:
+    ///
+    /// - Every page is yielded sorted by `valid_from` when `window` is at least the page size.
+    /// - An indicator is only yielded after a later one if it arrived more than `window`
+    ///   indicators after it, for example several pages later.
+    ///
+    /// Memory is bounded by one page plus `window` indicators. Indicators whose `valid_from`
+    /// ties are yielded in `id` order. A failed request yields the indicators still held in
+    /// the heap, then a single `Err` item, and ends the iteration.
+    ///
+    /// # Parameters
+    ///
+    /// - `collection_id`, `limit`, `private`, `added_after`, `matches`: As for
+    ///   [`get_cc_indicators`](Self::get_cc_indicators). `limit` sets the page size.
+    ///
+    /// - `window`: The number of indicators buffered for reordering, at least 1.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+    /// let ordered = agent.iter_cc_indicators_by_valid_from(
+    ///     None,
+    ///     Some(500),
+    ///     false,
+    ///     Some("2024-01-01T00:00:00Z"),
+    ///     &None,
+    ///     500,
+    /// );
+    /// for indicator in ordered {
+    ///     let indicator = indicator?;
+    ///     // Assign the indicator to its time bucket
+    /// }
+    /// ```
+    pub fn iter_cc_indicators_by_valid_from(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: &Option<HashMap<&str, &str>>,
+        window: usize,
+    ) -> impl Iterator<Item = Result<CCIndicator>> + '_ {
+        ValidFromOrder {
+            inner: self.iter_cc_indicators(collection_id, limit, private, added_after, matches),
+            heap: BinaryHeap::new(),
+            window: window.max(1),
+            error: None,
+        }
+    }
+
+    /// Retrieves the indicators of every collection of an API root, pulling several
+    /// collections concurrently.
+    ///
+    /// The collections of `root` are listed first. Their indicators are then pulled, following
+    /// pagination, on at most `parallelism` threads at once (see
+    /// [`with_parallelism`](Self::with_parallelism)). A collection that fails does not fail the
+    /// others: its error is kept in the result in place of its indicators.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The API root whose collections are pulled (e.g., `api`).
+    /// - `limit`: The maximum number of indicators per page (defaults to 1000 if `None`).
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(HashMap<String, Result<Vec<CCIndicator>>>)` mapping each collection ID to its
+    /// indicators or the error that stopped its pull.
+    /// Returns `Err(TaxiiError)` if the collections cannot be listed.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from `get_collections`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = CCTaxiiClient::new("my_username", "my_api_key").with_parallelism(8);
+    /// for (collection, result) in agent.get_all_indicators("api", None)? {
+    ///     match result {
+    ///         Ok(indicators) => println!("{collection}: {} indicators", indicators.len()),
+    ///         Err(e) => println!("{collection}: {e}"),
+    ///     }
+    /// }
+    /// ```
+    pub fn get_all_indicators(
+        &self,
+        root: &str,
+        limit: Option<usize>,
+    ) -> Result<HashMap<String, Result<Vec<CCIndicator>>>> {
+        let collections = self.get_collections(Some(root))?;
+        let workers = self.parallelism.clamp(1, collections.len().max(1));
+        let queue = Mutex::new(collections.into_iter());
+        let results = Mutex::new(HashMap::new());
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    while let Some(collection) = queue.lock().ok().and_then(|mut ids| ids.next()) {
+                        let url = self.collection_objects_url(root, &collection, limit, None, None);
+                        let mut indicators = Vec::new();
+                        let result = self
+                            .fetch_pages(&url, true, |page: CCEnvelope| {
+                                indicators.extend(page.objects);
+                                Ok(true)
+                            })
+                            .map(|()| indicators);
+                        if let Ok(mut results) = results.lock() {
+                            results.insert(collection, result);
+                        }
+                    }
+                });
+            }
+        });
+        Ok(results
+            .into_inner()
+            .unwrap_or_else(std::sync::PoisonError::into_inner))
+    }
+
+    /// Returns the private account root or the public `api` root.
+    ///
+    /// # Errors
+    ///
+    /// Returns `TaxiiCollectionError` if `private` is set but the client has no private root,
+    /// as for a bearer-token client created without an account.
+    fn root(&self, private: bool) -> Result<&str> {
+        if private {
+            self.private_root().ok_or_else(|| {
+                Box::new(TaxiiCollectionError(
+                    "No private API root configured".to_string(),
+                ))
+            })
+        } else {
+            Ok("api")
+        }
+    }
+
+    /// Returns the detailed collections of `root`, from the collections cache when it is
+    /// enabled and still fresh.
+    fn cached_collections(&self, root: &str) -> Result<Vec<Collection>> {
+        if let Some(ttl) = self.collections_ttl {
+            let cached = self.collections_cache.lock().ok().and_then(|cache| {
+                cache
+                    .get(root)
+                    .filter(|(listed, _)| listed.elapsed() < ttl)
+                    .map(|(_, collections)| collections.clone())
+            });
+            if let Some(collections) = cached {
+                return Ok(collections);
+            }
+        }
+        let collections = self.get_collections_detailed(root)?;
+        if self.collections_ttl.is_some() {
+            if let Ok(mut cache) = self.collections_cache.lock() {
+                cache.insert(root.to_string(), (Instant::now(), collections.clone()));
+            }
+        }
+        Ok(collections)
+    }
+
+    /// Returns `collection_id`, or the default collection of `root` if none is given, as
+    /// chosen by [`default_collection`].
+    fn resolve_collection(&self, collection_id: Option<&str>, root: &str) -> Result<String> {
+        match collection_id {
+            Some(id) => Ok(id.to_string()),
+            None => default_collection(self.cached_collections(root)?),
+        }
+    }
+
+    /// Returns `added_after`, or the client's default `added_after` if none is given. Only
+    /// methods taking a per-call `added_after` apply the default.
+    fn added_after_or_default<'a>(&'a self, added_after: Option<&'a str>) -> Option<&'a str> {
+        added_after.or(self.default_added_after.as_deref())
+    }
+
+    /// Builds the manifest endpoint URL for a collection. The manifest endpoint sits beside
+    /// the objects endpoint, so a custom objects path template is followed.
+    fn manifest_url(
+        &self,
+        root: &str,
+        collection: &str,
+        limit: usize,
+        added_after: Option<&str>,
+    ) -> String {
+        let objects = self.objects_endpoint(root, collection);
+        let base = objects.strip_suffix("objects/").unwrap_or(&objects);
+        let mut url = format!("{base}manifest/?limit={limit}");
+        if let Some(timestamp) = added_after {
+            url = format!("{url}&added_after={timestamp}");
+        }
+        url
+    }
+
+    /// Builds the objects endpoint URL for a collection, resolving the default collection
+    /// of the root when no `collection_id` is given.
+    fn objects_url(
+        &self,
+        collection_id: Option<&str>,
+        limit: Option<usize>,
+        private: bool,
+        added_after: Option<&str>,
+        matches: Option<&HashMap<&str, &str>>,
+    ) -> Result<String> {
+        validate_added_after(added_after)?;
+        validate_version_matches(matches)?;
+        let root = self.root(private)?;
+        let collection = self.resolve_collection(collection_id, root)?;
+        Ok(self.collection_objects_url(root, &collection, limit, added_after, matches))
+    }
+
+    /// Builds the objects endpoint URL with its query for a known root and collection.
+    fn collection_objects_url(
+        &self,
+        root: &str,
+        collection: &str,
+        limit: Option<usize>,
+        added_after: Option<&str>,
+        matches: Option<&HashMap<&str, &str>>,
+    ) -> String {
+        let query = objects_query(limit.unwrap_or(1000), added_after, matches, None);
+        format!("{}{query}", self.objects_endpoint(root, collection))
+    }
+
+    /// Requests the objects page at `url` and hands it to `on_page`, following the server's
+    /// pagination while `follow_pages` is set and more data is available.
+    ///
+    /// `on_page` returns whether to keep paginating, so callers can stop early. Each page URL
+    /// is `url` with only the latest `next` cursor added, which is treated as opaque: it is
+    /// percent-encoded exactly once, so the server receives back the exact value it sent,
+    /// even if that value is itself URL-encoded.
+    fn fetch_pages<P, F>(&self, base: &str, follow_pages: bool, mut on_page: F) -> Result<()>
+    where
+        P: Page,
+        F: FnMut(P) -> Result<bool>,
+    {
+        let mut url = base.to_string();
+        let mut more = true;
+        while more {
+            let page: P = self.fetch_page(&url)?;
+            more = follow_pages && page.more();
+            let next = page.next().map(str::to_string);
+            more &= on_page(page)?;
+            if let Some(cursor) = next {
+                url = format!("{base}{}", next_query(&cursor));
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Requests and parses a single page, reporting every failing object in strict mode.
+    ///
+    /// Every paginated pull requests its pages here, so this is where the client's cancel
+    /// token is checked.
+    fn fetch_page<P: Page>(&self, url: &str) -> Result<P> {
+        if self.is_cancelled() {
+            return Err(Box::new(Cancelled("Pull cancelled".to_string())));
+        }
+        let response = self.request_page(url)?;
+        match self.strict_failures {
+            Some(max) => {
+                let raw: serde_json::Value = self.parse_json(response)?;
+                P::deserialize(&raw).map_err(|e| {
+                    let failures = P::describe_failures(&raw, max);
+                    let message = if failures.is_empty() {
+                        e.to_string()
+                    } else {
+                        format!("{e}; failing objects: {}", failures.join("; "))
+                    };
+                    Box::new(JsonDeserializationError(message))
+                })
+            }
+            None => self.parse_json(response),
+        }
+    }
+}
+
+/// A lazy iterator over the indicators of a collection, requesting each page only once the
+/// indicators of the previous one have been consumed.
+struct CCIndicatorIter<'a> {
+    client: &'a CCTaxiiClient,
+    base: String,
+    url: Option<String>,
+    buffer: std::vec::IntoIter<CCIndicator>,
+    error: Option<Box<TaxiiError>>,
+}
+
+impl Iterator for CCIndicatorIter<'_> {
+    type Item = Result<CCIndicator>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(indicator) = self.buffer.next() {
+                return Some(Ok(indicator));
+            }
+            if let Some(error) = self.error.take() {
+                return Some(Err(error));
+            }
+            let url = self.url.take()?;
+            match self.client.fetch_page::<CCEnvelope>(&url) {
+                Ok(page) => {
+                    self.url = page
+                        .next()
+                        .filter(|_| page.more())
+                        .map(|cursor| format!("{}{}", self.base, next_query(cursor)));
+                    self.buffer = page.objects.into_iter();
+                }
+                Err(error) => return Some(Err(error)),
+            }
+        }
+    }
+}
+
+/// An indicator ordered by `valid_from`, then by `id`.
+struct ByValidFrom(CCIndicator);
+
+impl Ord for ByValidFrom {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_timestamps(&self.0.valid_from, &other.0.valid_from)
+            .then_with(|| self.0.id.cmp(&other.0.id))
+    }
+}
+
+impl PartialOrd for ByValidFrom {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for ByValidFrom {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for ByValidFrom {}
+
+/// Reorders the indicators of another iterator by `valid_from` through a heap holding at most
+/// `window` of them.
+struct ValidFromOrder<I> {
+    inner: I,
+    heap: BinaryHeap<Reverse<ByValidFrom>>,
+    window: usize,
+    error: Option<Box<TaxiiError>>,
+}
+
+impl<I: Iterator<Item = Result<CCIndicator>>> Iterator for ValidFromOrder<I> {
+    type Item = Result<CCIndicator>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.error.is_none() && self.heap.len() < self.window {
+            match self.inner.next() {
+                Some(Ok(indicator)) => self.heap.push(Reverse(ByValidFrom(indicator))),
+                Some(Err(error)) => self.error = Some(error),
+                None => break,
+            }
+        }
+        match self.heap.pop() {
+            Some(Reverse(ByValidFrom(indicator))) => Some(Ok(indicator)),
+            None => self.error.take().map(Err),
+        }
+    }
+}
+
+/// The header row written by `stream_cc_indicators_csv`.
+const CSV_HEADER: &str = "id,type,name,pattern,pattern_type,pattern_version,spec_version,created,modified,valid_from,confidence,description,labels\r\n";
+
+/// Formats an indicator as a CSV row matching `CSV_HEADER`, ending with CRLF.
+fn csv_row(indicator: &CCIndicator) -> String {
+    let confidence = indicator
+        .confidence
+        .map_or(String::new(), |confidence| confidence.to_string());
+    let fields = [
+        indicator.id.as_str(),
+        &indicator.r#type,
+        &indicator.name,
+        &indicator.pattern,
+        &indicator.pattern_type,
+        &indicator.pattern_version,
+        &indicator.spec_version,
+        &indicator.created,
+        &indicator.modified,
+        &indicator.valid_from,
+        &confidence,
+        indicator.description.as_deref().unwrap_or_default(),
+        &indicator.labels.join(";"),
+    ];
+    let row: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+    format!("{}\r\n", row.join(","))
+}
+
+/// Quotes a CSV field if it contains a comma, a quote, or a line break, doubling its quotes.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Parses a `Retry-After` header value, given either as a number of seconds or as an HTTP
+/// date. A date in the past yields no delay.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    value
+        .parse::<u64>()
+        .map(Duration::from_secs)
+        .ok()
+        .or_else(|| {
+            let date = httpdate::parse_http_date(value).ok()?;
+            Some(date.duration_since(SystemTime::now()).unwrap_or_default())
+        })
+}
+
+/// Returns the delay before retry `attempt`: a random duration between half and all of
+/// `base * 2^(attempt - 1)`.
+fn backoff(base: Duration, attempt: u32) -> Duration {
+    let delay = base.saturating_mul(2_u32.saturating_pow(attempt - 1));
+    let jitter = f64::from(uuid::Uuid::new_v4().as_fields().0) / f64::from(u32::MAX);
+    delay / 2 + delay.mul_f64(jitter / 2.0)
+}
+
+/// Builds a STIX 2.1 `sighting` SDO recording that `indicator` was observed.
+///
+/// The sighting gets a fresh random id, `created` and `modified` set to the current time, and
+/// `sighting_of_ref` pointing at the indicator. `where_sighted_refs` are the ids of the
+/// `identity` or `location` objects where it was seen, and are omitted when empty. The result
+/// is a template, so further properties such as `count` or `first_seen` can be added before
+/// submitting it.
+///
+/// # Parameters
+///
+/// - `indicator`: The indicator that was observed.
+/// - `where_sighted_refs`: The ids of the identities or locations where it was seen.
+///
+/// # Examples
+///
+/// ```
+/// let sighting = to_sighting_template(&indicator, &["identity--f431f809-377b-45e0-aa1c-6a4751cae5ff"]);
+/// ```
+#[must_use]
+pub fn to_sighting_template(
+    indicator: &CCIndicator,
+    where_sighted_refs: &[&str],
+) -> serde_json::Value {
+    let now = humantime::format_rfc3339_millis(SystemTime::now()).to_string();
+    let mut sighting = serde_json::json!({
+        "type": "sighting",
+        "spec_version": "2.1",
+        "id": format!("sighting--{}", uuid::Uuid::new_v4()),
+        "created": now,
+        "modified": now,
+        "sighting_of_ref": indicator.id,
+    });
+    if !where_sighted_refs.is_empty() {
+        sighting["where_sighted_refs"] = serde_json::json!(where_sighted_refs);
+    }
+    sighting
+}
+
+/// Compares two STIX timestamps chronologically.
+///
+/// STIX timestamps are RFC 3339 UTC values whose fractional seconds may have any precision,
+/// so plain string comparison would order `00.5Z` before `00Z`. Fractional digits are compared
+/// as if padded with zeros instead.
+fn compare_timestamps(a: &str, b: &str) -> Ordering {
+    fn split(timestamp: &str) -> (&str, &str) {
+        let timestamp = timestamp.trim_end_matches(['Z', 'z']);
+        timestamp.split_once('.').unwrap_or((timestamp, ""))
+    }
+    let (a_seconds, a_fraction) = split(a);
+    let (b_seconds, b_fraction) = split(b);
+    let width = a_fraction.len().max(b_fraction.len());
+    a_seconds
+        .cmp(b_seconds)
+        .then_with(|| format!("{a_fraction:0<width$}").cmp(&format!("{b_fraction:0<width$}")))
+}
+
+/// Builds the URL of the objects endpoint of a collection, using the standard TAXII 2.1 path.
+///
+/// This is the URL `get_cc_indicators` requests for the given parameters, without making a
+/// request, so it can be logged or checked on its own. The query parameters always come in
+/// the same order: `limit`, `added_after`, the `match[...]` filters sorted by field, and
+/// finally the `next` cursor, which is percent-encoded.
+///
+/// # Parameters
+///
+/// - `root`: The API root (e.g., `api` or the account name).
+/// - `collection`: The collection ID.
+/// - `limit`: The maximum number of objects per page.
+/// - `added_after`: Only objects added after this timestamp are requested, if given.
+/// - `matches`: Filters sent as `match[field]=value` parameters, if given.
+/// - `next`: The cursor of the page to request, as returned by the server, if given.
+///
+/// # Examples
+///
+/// ```
+/// let url = build_objects_url("api", "c1", 100, None, &None, Some("page-2"));
+/// assert_eq!(url, "api/collections/c1/objects/?limit=100&next=page%2D2");
+/// ```
+#[must_use]
+#[allow(clippy::implicit_hasher)]
+pub fn build_objects_url(
+    root: &str,
+    collection: &str,
+    limit: usize,
+    added_after: Option<&str>,
+    matches: &Option<HashMap<&str, &str>>,
+    next: Option<&str>,
+) -> String {
+    let query = objects_query(limit, added_after, matches.as_ref(), next);
+    format!("{root}/collections/{collection}/objects/{query}")
+}
+
+/// Builds the query string of an objects request, starting with `?`.
+fn objects_query(
+    limit: usize,
+    added_after: Option<&str>,
+    matches: Option<&HashMap<&str, &str>>,
+    next: Option<&str>,
+) -> String {
+    let mut query = format!("?limit={limit}");
+    if let Some(timestamp) = added_after {
+        query = format!("{query}&added_after={timestamp}");
+    }
+    query += &matches.map_or(String::new(), match_query);
+    query += &next.map_or(String::new(), next_query);
+    query
+}
+
+/// Builds the query parameter carrying a pagination cursor.
+fn next_query(cursor: &str) -> String {
+    format!("&next={}", utf8_percent_encode(cursor, NON_ALPHANUMERIC))
+}
+
+/// Checks that an `added_after` argument is an RFC 3339 timestamp, since servers silently
+/// return nothing for malformed ones.
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) fn validate_added_after(added_after: Option<&str>) -> Result<()> {
+    match added_after {
+        Some(timestamp) if !is_rfc3339(timestamp) => Err(Box::new(InvalidFilter(format!(
+            "added_after must be an RFC 3339 timestamp, got {timestamp}"
+        )))),
+        _ => Ok(()),
+    }
+}
+
+/// Picks the [default collection](CCTaxiiClient#default-collection) of an API root.
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) fn default_collection(collections: Vec<Collection>) -> Result<String> {
+    collections
+        .into_iter()
+        .filter(|collection| collection.supports_media_type(STIX_MEDIA_TYPE))
+        .map(|collection| collection.id)
+        .min()
+        .ok_or_else(|| Box::new(TaxiiCollectionError("No collections available".to_string())))
+}
+
+/// Checks that `value` is an RFC 3339 timestamp, such as `2024-01-01T00:00:00Z` or
+/// `2024-01-01T01:00:00.5+01:00`.
+fn is_rfc3339(value: &str) -> bool {
+    fn number(value: &str, range: std::ops::Range<usize>, max: u32) -> Option<u32> {
+        value
+            .get(range)
+            .filter(|digits| digits.bytes().all(|b| b.is_ascii_digit()))
+            .and_then(|digits| digits.parse().ok())
+            .filter(|&n| n <= max)
+    }
+    let separators = [(4, b'-'), (7, b'-'), (13, b':'), (16, b':')];
+    if !separators
+        .iter()
+        .all(|&(i, separator)| value.as_bytes().get(i) == Some(&separator))
+        || !matches!(value.as_bytes().get(10), Some(b'T' | b't'))
+    {
+        return false;
+    }
+    let (Some(year), Some(month), Some(day)) = (
+        number(value, 0..4, 9999),
+        number(value, 5..7, 12),
+        number(value, 8..10, 31),
+    ) else {
+        return false;
+    };
+    let days_in_month = match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    };
+    let time = [
+        number(value, 11..13, 23),
+        number(value, 14..16, 59),
+        number(value, 17..19, 60),
+    ];
+    let Some(rest) = value.get(19..) else {
+        return false;
+    };
+    let offset = match rest.strip_prefix('.') {
+        Some(fraction) => {
+            let digits = fraction.bytes().take_while(u8::is_ascii_digit).count();
+            if digits == 0 {
+                return false;
+            }
+            &fraction[digits..]
+        }
+        None => rest,
+    };
+    let valid_offset = matches!(offset, "Z" | "z")
+        || (matches!(offset.as_bytes().first(), Some(b'+' | b'-'))
+            && offset.len() == 6
+            && offset.as_bytes()[3] == b':'
+            && number(offset, 1..3, 23).is_some()
+            && number(offset, 4..6, 59).is_some());
+    month >= 1
+        && (1..=days_in_month).contains(&day)
+        && time.iter().all(Option::is_some)
+        && valid_offset
+}
+
+/// Checks the comma-separated values of the `match[version]` and `match[spec_version]`
+/// filters, which must be `VersionFilter` values and `<major>.<minor>` versions.
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) fn validate_version_matches(matches: Option<&HashMap<&str, &str>>) -> Result<()> {
+    let Some(matches) = matches else {
+        return Ok(());
+    };
+    if let Some(versions) = matches.get("version") {
+        for version in versions.split(',') {
+            VersionFilter::parse(version)?;
+        }
+    }
+    if let Some(versions) = matches.get("spec_version") {
+        for version in versions.split(',') {
+            let is_number = |n: &str| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit());
+            let valid = version
+                .split_once('.')
+                .is_some_and(|(major, minor)| is_number(major) && is_number(minor));
+            if !valid {
+                return Err(Box::new(InvalidFilter(format!(
+                    "match[spec_version] must be a version such as 2.1, got {version}"
+                ))));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Builds the `&match[key]=value` query parameters for a set of filters, sorted by key.
+fn match_query(matches: &HashMap<&str, &str>) -> String {
+    let mut filters: Vec<(&&str, &&str)> = matches.iter().collect();
+    filters.sort_unstable();
+    filters
+        .into_iter()
+        .fold(String::new(), |acc, (k, v)| format!("{acc}&match[{k}]={v}"))
+}
+
+/// A reader over a response body that counts the bytes read through it and, if enabled,
+/// hashes them for `Content-Digest` verification.
+struct BodyReader<R> {
+    inner: R,
+    count: u64,
+    sha256: Option<Sha256>,
+    sha512: Option<Sha512>,
+}
+
+impl<R: Read> BodyReader<R> {
+    fn new(inner: R, hash: bool) -> Self {
+        Self {
+            inner,
+            count: 0,
+            sha256: hash.then(Sha256::new),
+            sha512: hash.then(Sha512::new),
+        }
+    }
+
+    /// Checks the bytes read so far against a `Content-Digest` header value.
+    fn verify(self, header: &str) -> Result<()> {
+        let sha256 = self.sha256.map(|hasher| base64::encode(&hasher.finalize()));
+        let sha512 = self.sha512.map(|hasher| base64::encode(&hasher.finalize()));
+        verify_content_digest(header, |algorithm| match algorithm {
+            "sha-256" => sha256.clone(),
+            "sha-512" => sha512.clone(),
+            _ => None,
+        })
+    }
+}
+
+impl<R: Read> Read for BodyReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count += read as u64;
+        if let Some(hasher) = &mut self.sha256 {
+            hasher.update(&buf[..read]);
+        }
+        if let Some(hasher) = &mut self.sha512 {
+            hasher.update(&buf[..read]);
+        }
+        Ok(read)
+    }
+}
+
+/// A page of objects that carries TAXII pagination information.
+pub trait Page: DeserializeOwned {
+    /// Whether the server indicated that more data is available.
+    fn more(&self) -> bool;
+
+    /// The pagination cursor for the next page, if any.
+    fn next(&self) -> Option<&str>;
+
+    /// Describes up to `max` objects of a raw page that fail to deserialize, each with its id
+    /// and raw JSON. Pages without typed objects report none.
+    fn describe_failures(_page: &serde_json::Value, _max: usize) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+impl<T: DeserializeOwned> Page for Envelope<T> {
+    fn more(&self) -> bool {
+        self.more()
+    }
+
+    fn next(&self) -> Option<&str> {
+        self.next()
+    }
+
+    fn describe_failures(page: &serde_json::Value, max: usize) -> Vec<String> {
+        let objects = match &page["objects"] {
+            serde_json::Value::Array(objects) => objects.iter().collect(),
+            serde_json::Value::Null => Vec::new(),
+            object => vec![object],
+        };
+        objects
+            .into_iter()
+            .filter_map(|object| {
+                T::deserialize(object).err().map(|e| {
+                    let id = object["id"].as_str().unwrap_or("<no id>");
+                    format!("{id}: {e} in {object}")
+                })
+            })
+            .take(max)
+            .collect()
+    }
+}
+
+impl Page for Manifest {
+    fn more(&self) -> bool {
+        self.more.unwrap_or(false)
+    }
+
+    fn next(&self) -> Option<&str> {
+        self.next.as_deref()
+    }
+}
+
+impl Page for serde_json::Value {
+    fn more(&self) -> bool {
+        self["more"].as_bool().unwrap_or(false)
+    }
+
+    fn next(&self) -> Option<&str> {
+        self["next"].as_str()
+    }
+}
+
+/// Validates a base URL and strips its trailing slashes.
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) fn normalize_base_url(base_url: &str) -> Result<String> {
+    let invalid = |reason: &str| Box::new(InvalidBaseUrl(format!("{base_url}: {reason}")));
+    let rest = base_url
+        .strip_prefix("https://")
+        .or_else(|| base_url.strip_prefix("http://"))
+        .ok_or_else(|| invalid("scheme must be http or https"))?;
+    if rest.contains(['?', '#']) {
+        return Err(invalid("query and fragment are not allowed"));
+    }
+    let authority = rest.split('/').next().unwrap_or_default();
+    let (host, port) = authority
+        .rsplit_once(':')
+        .filter(|(_, port)| !port.contains(']'))
+        .map_or((authority, None), |(host, port)| (host, Some(port)));
+    let host_valid = host
+        .strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .map_or_else(
+            || {
+                !host.is_empty()
+                    && host.split('.').all(|label| {
+                        !label.is_empty()
+                            && !label.starts_with('-')
+                            && !label.ends_with('-')
+                            && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+                    })
+            },
+            |ip| ip.parse::<std::net::Ipv6Addr>().is_ok(),
+        );
+    if !host_valid {
+        return Err(invalid("missing or malformed host"));
+    }
+    if port.is_some_and(|port| port.parse::<u16>().is_err()) {
+        return Err(invalid("port must be a number"));
+    }
+    Ok(base_url.trim_end_matches('/').to_string())
+}
+
+/// Builds a `Basic` Authorization header value from a username and API key.
+#[allow(clippy::redundant_pub_crate)]
+pub(crate) fn basic_auth(username: &str, api_key: &str) -> String {
+    let key = format!("{username}:{api_key}");
+    format!("Basic {}", base64::encode(key.as_bytes()))
+}
+
+/// Checks a body against a `Content-Digest` header value (RFC 9530).
+///
+/// The header may list several `algorithm=:base64:` entries. Every entry using a supported
+/// algorithm must match; unsupported algorithms are ignored. `digest_of` returns the base64
+/// digest of the body for a lowercase algorithm name, or `None` if it is not supported.
+fn verify_content_digest<F>(header: &str, digest_of: F) -> Result<()>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    for entry in header.split(',') {
+        let Some((algorithm, value)) = entry.trim().split_once('=') else {
+            continue;
+        };
+        let expected = value.trim().trim_matches(':');
+        let Some(actual) = digest_of(&algorithm.trim().to_ascii_lowercase()) else {
+            continue;
+        };
+        if actual != expected {
+            return Err(Box::new(IntegrityError(format!(
+                "{algorithm} digest mismatch: expected {expected}, got {actual}"
+            ))));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::significant_drop_tightening)]
+mod tests {
+    use super::*;
+    use crate::fixtures;
+    use std::env;
+
+    const DISCOVERY_BODY: &str = r#"{"api_roots":["/api/"],"contact":"it.support@cloudcover.net","default":"/api/","description":"Mock TAXII server","title":"CloudCover TAXII Server"}"#;
+
+    const COLLECTIONS_BODY: &str = r#"{"collections":[{"can_read":true,"can_write":false,"id":"c1","media_types":["application/stix+json;version=2.1"],"name":"mock","title":"Mock collection"}]}"#;
+
+    fn indicator_json(id: &str) -> String {
+        versioned_indicator_json(id, "2024-01-01T00:00:00.000Z")
+    }
+
+    fn versioned_indicator_json(id: &str, modified: &str) -> String {
+        fixtures::indicator_value(id, modified, fixtures::PATTERN).to_string()
+    }
+
+    fn envelope_json(ids: &[&str], next: Option<&str>) -> String {
+        let objects: Vec<String> = ids.iter().map(|id| indicator_json(id)).collect();
+        envelope_of(&objects, next)
+    }
+
+    fn envelope_of(objects: &[String], next: Option<&str>) -> String {
+        next.map_or_else(
+            || format!(r#"{{"more":false,"objects":[{}]}}"#, objects.join(",")),
+            |next| {
+                format!(
+                    r#"{{"more":true,"next":"{next}","objects":[{}]}}"#,
+                    objects.join(",")
+                )
+            },
+        )
+    }
+
+    fn mock_client(url: &str) -> CCTaxiiClient {
+        CCTaxiiClient::new("user", "key")
+            .with_base_url(url)
+            .expect("Invalid mock URL")
+    }
+
+    #[test]
+    fn get_discovery_test() {
+        dotenv::dotenv().ok();
+        let username = env::var("TAXII_USERNAME").expect("You've not set the TAXII_USERNAME");
+        let api_key = env::var("TAXII_API_KEY").expect("You've not set the TAXII_API_KEY");
+        let agent = CCTaxiiClient::new(&username, &api_key);
+        let discovery = agent.get_discovery().expect("Failed to get discovery");
+
+        assert_eq!(discovery.api_roots, ["/api/"], "API roots are incorrect");
+        assert_eq!(
+            discovery.contact, "it.support@cloudcover.net",
+            "Contact email is incorrect"
+        );
+        assert_eq!(discovery.default, "/api/", "Default is incorrect");
+        assert_eq!(discovery.description, "This API ROOT contains TAXII 2.1 REST API endpoints that serve CloudCover STIX 2.1 data", "Description is incorrect");
+        assert_eq!(
+            discovery.title, "CloudCover TAXII Server",
+            "Title is incorrect"
+        );
+    }
+
+    #[test]
+    fn get_collections_test() {
+        dotenv::dotenv().ok();
+        let username = env::var("TAXII_USERNAME").expect("You've not set the TAXII_USERNAME");
+        let api_key = env::var("TAXII_API_KEY").expect("You've not set the TAXII_API_KEY");
+        let agent = CCTaxiiClient::new(&username, &api_key);
+        let collections = agent
+            .get_collections(Some("api"))
+            .expect("Failed to get collections");
+        assert_eq!(collections.len(), 1);
+    }
+
+    #[test]
+    fn get_indicators_test() {
+        dotenv::dotenv().ok();
+        let username = env::var("TAXII_USERNAME").expect("You've not set the TAXII_USERNAME");
+        let api_key = env::var("TAXII_API_KEY").expect("You've not set the TAXII_API_KEY");
+        let agent = CCTaxiiClient::new(&username, &api_key);
+        let indicators = agent
+            .get_cc_indicators(None, Some(5), false, None, &None, false)
+            .expect("Failed to get objects");
+        assert_eq!(indicators.len(), 5);
+    }
+
+    #[test]
+    fn verify_digest_match_test() {
+        let mut server = mockito::Server::new();
+        let digest = format!(
+            "sha-256=:{}:",
+            base64::encode(&Sha256::digest(DISCOVERY_BODY.as_bytes()))
+        );
+        let mock = server
+            .mock("GET", "/taxii2/")
+            .with_header("Content-Digest", &digest)
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = mock_client(&server.url()).with_verify_digest(true);
+        let discovery = agent.get_discovery().expect("Failed to get discovery");
+        assert_eq!(discovery.title, "CloudCover TAXII Server");
+        mock.assert();
+    }
+
+    #[test]
+    fn verify_digest_mismatch_test() {
+        let mut server = mockito::Server::new();
+        let digest = format!(
+            "sha-256=:{}:",
+            base64::encode(&Sha256::digest(b"something else"))
+        );
+        server
+            .mock("GET", "/taxii2/")
+            .with_header("Content-Digest", &digest)
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = mock_client(&server.url()).with_verify_digest(true);
+        let error = agent
+            .get_discovery()
+            .expect_err("Digest mismatch was accepted");
+        assert!(matches!(*error, IntegrityError(_)));
+
+        let agent = mock_client(&server.url());
+        assert!(
+            agent.get_discovery().is_ok(),
+            "Digest checked while disabled"
+        );
+    }
+
+    #[test]
+    fn batched_indicators_test() {
+        let mut server = mockito::Server::new();
+        let first = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_json(&["a", "b", "c"], Some("p2")))
+            .expect(1)
+            .create();
+        let second = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(envelope_json(&["d", "e"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let mut batches: Vec<Vec<String>> = Vec::new();
+        agent
+            .batched_cc_indicators(Some("c1"), Some(3), false, None, &None, 2, |batch| {
+                batches.push(batch.iter().map(|i| i.id.clone()).collect());
+                Ok(())
+            })
+            .expect("Failed to get batches");
+        assert_eq!(batches, [vec!["a", "b"], vec!["c", "d"], vec!["e"]]);
+        first.assert();
+        second.assert();
+    }
+
+    #[test]
+    fn private_credentials_test() {
+        let mut server = mockito::Server::new();
+        let public = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .match_header("Authorization", basic_auth("user", "key").as_str())
+            .with_body(envelope_json(&["a"], None))
+            .create();
+        let private = server
+            .mock("GET", "/owner/collections/c2/objects/")
+            .match_query(mockito::Matcher::Any)
+            .match_header("Authorization", basic_auth("owner", "secret").as_str())
+            .with_body(envelope_json(&["b"], None))
+            .create();
+        let agent = mock_client(&server.url()).with_private_credentials("owner", "secret");
+        let indicators = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, false)
+            .expect("Failed to get public objects");
+        assert_eq!(indicators[0].id, "a");
+        let indicators = agent
+            .get_cc_indicators(Some("c2"), None, true, None, &None, false)
+            .expect("Failed to get private objects");
+        assert_eq!(indicators[0].id, "b");
+        public.assert();
+        private.assert();
+    }
+
+    #[test]
+    fn confidence_test() {
+        let scored = indicator_json("a").replacen('{', r#"{"confidence":85,"#, 1);
+        let indicator: CCIndicator =
+            serde_json::from_str(&scored).expect("Failed to parse indicator");
+        assert_eq!(indicator.confidence(), Some(85));
+
+        let unscored: CCIndicator =
+            serde_json::from_str(&indicator_json("b")).expect("Failed to parse indicator");
+        assert_eq!(unscored.confidence(), None);
+
+        let out_of_range = indicator_json("c").replacen('{', r#"{"confidence":101,"#, 1);
+        assert!(serde_json::from_str::<CCIndicator>(&out_of_range).is_err());
+
+        let kept = filter_min_confidence(vec![indicator, unscored], 80);
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].id, "a");
+        assert!(filter_min_confidence(kept, 90).is_empty());
+    }
+
+    #[test]
+    fn chunked_response_test() {
+        let mut server = mockito::Server::new();
+        let ids: Vec<String> = (0..200).map(|i| format!("indicator--{i}")).collect();
+        let body = envelope_json(&ids.iter().map(String::as_str).collect::<Vec<_>>(), None);
+        let mock = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_chunked_body(move |writer| {
+                for chunk in body.as_bytes().chunks(1000) {
+                    writer.write_all(chunk)?;
+                    writer.flush()?;
+                }
+                Ok(())
+            })
+            .expect(2)
+            .create();
+        let agent = mock_client(&server.url());
+        let indicators = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, false)
+            .expect("Failed to read chunked response");
+        assert_eq!(indicators.len(), 200);
+        assert_eq!(indicators[199].id, "indicator--199");
+
+        let agent = mock_client(&server.url()).with_verify_digest(true);
+        let indicators = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, false)
+            .expect("Failed to read chunked response");
+        assert_eq!(indicators.len(), 200);
+        mock.assert();
+    }
+
+    #[cfg(feature = "reqwest-backend")]
+    #[test]
+    fn reqwest_backend_discovery_test() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/taxii2/")
+            .match_header("Accept", "application/taxii+json;version=2.1")
+            .with_header("Content-Type", "application/taxii+json;version=2.1")
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = mock_client(&server.url());
+        let discovery = agent.get_discovery().expect("Failed to get discovery");
+        assert_eq!(discovery.api_roots, ["/api/"]);
+        assert_eq!(discovery.title, "CloudCover TAXII Server");
+        mock.assert();
+    }
+
+    #[test]
+    fn raw_pages_test() {
+        let mut server = mockito::Server::new();
+        let first_body = envelope_json(&["a", "b"], Some("p2"));
+        let second_body = envelope_json(&["c"], None);
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(&first_body)
+            .expect(1)
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(&second_body)
+            .create();
+        let agent = mock_client(&server.url());
+        let (indicators, pages) = agent
+            .get_cc_indicators_with_raw(Some("c1"), None, false, None, &None, true)
+            .expect("Failed to get raw pages");
+        assert_eq!(indicators.len(), 3);
+        let expected: Vec<serde_json::Value> = [first_body, second_body]
+            .iter()
+            .map(|body| serde_json::from_str(body).expect("Invalid mock body"))
+            .collect();
+        assert_eq!(pages, expected);
+    }
+
+    #[test]
+    fn match_query_order_test() {
+        let filters = [
+            ("type", "indicator"),
+            ("id", "indicator--1"),
+            ("spec_version", "2.1"),
+            ("version", "last"),
+        ];
+        let forward: HashMap<&str, &str> = filters.into_iter().collect();
+        let backward: HashMap<&str, &str> = filters.into_iter().rev().collect();
+        let expected = "&match[id]=indicator--1&match[spec_version]=2.1&match[type]=indicator&match[version]=last";
+        assert_eq!(match_query(&forward), expected);
+        assert_eq!(match_query(&backward), expected);
+    }
+
+    #[test]
+    fn indicators_map_test() {
+        let mut server = mockito::Server::new();
+        let objects = [
+            versioned_indicator_json("indicator--1", "2024-01-01T00:00:00Z"),
+            versioned_indicator_json("indicator--2", "2024-01-01T00:00:00Z"),
+            versioned_indicator_json("indicator--1", "2024-01-01T00:00:00.5Z"),
+            versioned_indicator_json("indicator--1", "2024-01-01T00:00:00.25Z"),
+        ];
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_of(&objects, None))
+            .create();
+        let agent = mock_client(&server.url());
+        let indicators = agent
+            .get_cc_indicators_map(Some("c1"), None, false, None, &None)
+            .expect("Failed to get indicators");
+        assert_eq!(indicators.len(), 2);
+        assert_eq!(
+            indicators["indicator--1"].modified,
+            "2024-01-01T00:00:00.5Z"
+        );
+        assert_eq!(indicators["indicator--2"].modified, "2024-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn partial_deadline_test() {
+        let mut server = mockito::Server::new();
+        let first_body = envelope_json(&["a", "b"], Some("p2"));
+        let first = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_chunked_body(move |writer| {
+                std::thread::sleep(Duration::from_millis(300));
+                writer.write_all(first_body.as_bytes())
+            })
+            .expect(1)
+            .create();
+        let second = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(envelope_json(&["c"], None))
+            .expect(0)
+            .create();
+        let agent = mock_client(&server.url());
+        let deadline = Instant::now() + Duration::from_millis(100);
+        let pull = agent
+            .get_cc_indicators_partial(Some("c1"), None, false, None, &None, Some(deadline))
+            .expect("Failed to get indicators");
+        assert!(pull.timed_out);
+        assert_eq!(pull.indicators.len(), 2);
+        assert_eq!(pull.next.as_deref(), Some("p2"));
+        first.assert();
+        second.assert();
+
+        let pull = agent
+            .get_cc_indicators_partial(Some("c1"), None, false, None, &None, None)
+            .expect("Failed to get indicators");
+        assert!(!pull.timed_out);
+        assert_eq!(pull.indicators.len(), 3);
+        assert_eq!(pull.next, None);
+    }
+
+    #[test]
+    fn self_test_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/taxii2/")
+            .with_body(DISCOVERY_BODY)
+            .create();
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(COLLECTIONS_BODY)
+            .create();
+        let objects = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_json(&["a"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let report = agent.self_test(false).expect("Failed to run self-test");
+        assert!(report.success());
+        let names: Vec<&str> = report.steps.iter().map(|step| step.name).collect();
+        assert_eq!(names, ["discovery", "collections", "objects"]);
+
+        objects.remove();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .create();
+        let report = agent.self_test(false).expect("Failed to run self-test");
+        assert!(!report.success());
+        assert!(report.steps[0].success);
+        assert!(report.steps[1].success);
+        assert!(!report.steps[2].success);
+        assert!(report.steps[2].error.is_some());
+        let agent = CCTaxiiClient::with_bearer_token("my_token", None)
+            .with_base_url(server.url())
+            .expect("Invalid base URL");
+        assert!(matches!(
+            agent.self_test(true).map(|_| ()).map_err(|e| *e),
+            Err(TaxiiCollectionError(_))
+        ));
+    }
+
+    #[test]
+    fn path_templates_test() {
+        let mut server = mockito::Server::new();
+        let collections = server
+            .mock("GET", "/taxii/api/feeds/")
+            .with_body(COLLECTIONS_BODY)
+            .create();
+        let objects = server
+            .mock("GET", "/taxii/api/feeds/c1/items/")
+            .match_query(mockito::Matcher::UrlEncoded("limit".into(), "10".into()))
+            .with_body(envelope_json(&["a"], None))
+            .create();
+        let agent = mock_client(&server.url())
+            .with_collections_path("taxii/{root}/feeds/")
+            .with_objects_path("taxii/{root}/feeds/{collection}/items/");
+        let indicators = agent
+            .get_cc_indicators(None, Some(10), false, None, &None, false)
+            .expect("Failed to get indicators");
+        assert_eq!(indicators.len(), 1);
+        collections.assert();
+        objects.assert();
+
+        let raw = server
+            .mock("GET", "/taxii/api/feeds/c1/items/")
+            .match_query(mockito::Matcher::Missing)
+            .with_body(envelope_json(&["b"], None))
+            .create();
+        let dyn_agent: &dyn TaxiiClient = &agent;
+        let envelope = dyn_agent
+            .get_objects_json("api", "c1")
+            .expect("Failed to get objects");
+        assert_eq!(envelope.objects()[0]["id"], "b");
+        raw.assert();
+    }
+
+    #[test]
+    fn common_headers_test() {
+        let agent = CCTaxiiClient::new("user", "key");
+        let headers = agent.common_headers();
+        assert!(headers.contains(&("Content-Type", "application/taxii+json;version=2.1")));
+        assert!(headers.contains(&("Authorization", "[REDACTED]")));
+        assert!(!headers
+            .iter()
+            .any(|(_, value)| value.contains(&basic_auth("user", "key"))));
+    }
+
+    #[test]
+    fn sync_by_version_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{"objects":[
+                    {"id":"a","date_added":"2024-01-01T00:00:00Z","version":"2024-01-01T00:00:00Z"},
+                    {"id":"b","date_added":"2024-01-01T00:00:00Z","version":"2024-02-01T00:00:00Z"},
+                    {"id":"c","date_added":"2024-02-01T00:00:00Z","version":"2024-02-01T00:00:00Z"}
+                ]}"#,
+            )
+            .create();
+        let objects = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "match[id]".into(),
+                "b,c".into(),
+            ))
+            .with_body(envelope_json(&["b", "c"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let known = HashMap::from([
+            ("a".to_string(), "2024-01-01T00:00:00Z".to_string()),
+            ("b".to_string(), "2024-01-01T00:00:00Z".to_string()),
+        ]);
+        let (indicators, versions) = agent
+            .sync_cc_indicators(Some("c1"), false, &known)
+            .expect("Failed to sync indicators");
+        let ids: Vec<&str> = indicators.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["b", "c"]);
+        assert_eq!(versions.len(), 3);
+        assert_eq!(versions["b"], "2024-02-01T00:00:00Z");
+        objects.assert();
+    }
+
+    #[test]
+    fn request_method_post_test() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("POST", "/api/collections/c1/objects/")
+            .match_header("authorization", mockito::Matcher::Any)
+            .match_body(r#"{"objects":[]}"#)
+            .with_status(202)
+            .with_body(r#"{"id":"status-1"}"#)
+            .create();
+        let agent = mock_client(&server.url());
+        let response = agent
+            .request_method(
+                "POST",
+                "api/collections/c1/objects/",
+                Some(br#"{"objects":[]}"#),
+            )
+            .expect("Failed to send POST request");
+        assert_eq!(response.status(), 202);
+        mock.assert();
+    }
+
+    #[test]
+    fn default_malicious_ips_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(COLLECTIONS_BODY)
+            .create();
+        let objects: Vec<String> = [
+            ("a", "[ipv4-addr:value = '198.51.100.1']"),
+            ("b", "[ipv6-addr:value = '2001:db8::1']"),
+            ("c", "[ipv4-addr:value = '198.51.100.1']"),
+            ("d", "[ipv4-addr:value = '198.51.100.0/24']"),
+            ("e", "[domain-name:value = 'example.com']"),
+            ("f", "[ipv4-addr:value != '203.0.113.9']"),
+        ]
+        .iter()
+        .map(|(id, pattern)| {
+            indicator_json(id).replace("[ipv4-addr:value = '198.51.100.1']", pattern)
+        })
+        .collect();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_of(&objects, None))
+            .create();
+        let agent = mock_client(&server.url());
+        let ips = agent
+            .get_default_malicious_ips(false)
+            .expect("Failed to get malicious IPs");
+        let expected: Vec<IpAddr> = vec![
+            "198.51.100.1".parse().expect("Invalid IPv4 address"),
+            "2001:db8::1".parse().expect("Invalid IPv6 address"),
+        ];
+        assert_eq!(ips, expected);
+    }
+
+    #[test]
+    fn indicators_from_sources_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_json(&["a", "b"], None))
+            .create();
+        server
+            .mock("GET", "/user/collections/c2/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_json(&["c"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let public = SourceInfo {
+            root: "api".to_string(),
+            collection_id: "c1".to_string(),
+        };
+        let private = SourceInfo {
+            root: "user".to_string(),
+            collection_id: "c2".to_string(),
+        };
+        let tagged = agent
+            .get_cc_indicators_from_sources(&[public.clone(), private.clone()], None, &None, true)
+            .expect("Failed to get indicators");
+        let tags: Vec<(&SourceInfo, &str)> = tagged
+            .iter()
+            .map(|(source, indicator)| (source, indicator.id.as_str()))
+            .collect();
+        assert_eq!(tags, [(&public, "a"), (&public, "b"), (&private, "c")]);
+    }
+
+    #[test]
+    fn token_refresh_test() {
+        let mut server = mockito::Server::new();
+        let basic = mockito::Matcher::Regex("^Basic ".to_string());
+        let first = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .match_header("authorization", basic.clone())
+            .with_body(envelope_json(&["a"], Some("p2")))
+            .create();
+        let expired = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .match_header("authorization", basic)
+            .with_status(401)
+            .create();
+        let refreshed = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .match_header("authorization", "Bearer fresh")
+            .with_body(envelope_json(&["b"], None))
+            .create();
+        let agent = mock_client(&server.url()).with_token_refresh(|| "fresh".to_string());
+        let indicators = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, true)
+            .expect("Failed to get indicators after refresh");
+        let ids: Vec<&str> = indicators.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+        first.assert();
+        expired.assert();
+        refreshed.assert();
+
+        let mut server = mockito::Server::new();
+        let private_basic = basic_auth("private_user", "private_key");
+        let first = server
+            .mock("GET", "/private_user/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .match_header("authorization", private_basic.as_str())
+            .with_body(envelope_json(&["a"], Some("p2")))
+            .create();
+        let expired = server
+            .mock("GET", "/private_user/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .match_header("authorization", private_basic.as_str())
+            .with_status(401)
+            .create();
+        let refreshed = server
+            .mock("GET", "/private_user/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .match_header("authorization", "Bearer fresh")
+            .with_body(envelope_json(&["b"], None))
+            .create();
+        let agent = mock_client(&server.url())
+            .with_private_credentials("private_user", "private_key")
+            .with_token_refresh(|| "fresh".to_string());
+        let indicators = agent
+            .get_cc_indicators(Some("c1"), None, true, None, &None, true)
+            .expect("Failed to get private indicators after refresh");
+        assert_eq!(indicators.len(), 2);
+        first.assert();
+        expired.assert();
+        refreshed.assert();
+    }
+
+    #[test]
+    fn sighting_template_test() {
+        let indicator: CCIndicator =
+            serde_json::from_str(&indicator_json("indicator--a")).expect("Failed to parse");
+        let sighting = to_sighting_template(&indicator, &["identity--b"]);
+        assert_eq!(sighting["type"], "sighting");
+        assert_eq!(sighting["spec_version"], "2.1");
+        assert_eq!(sighting["sighting_of_ref"], indicator.id.as_str());
+        assert_eq!(
+            sighting["where_sighted_refs"],
+            serde_json::json!(["identity--b"])
+        );
+        assert_eq!(sighting["created"], sighting["modified"]);
+        let id = sighting["id"]
+            .as_str()
+            .expect("Sighting id is not a string");
+        let uuid = id.strip_prefix("sighting--").expect("Wrong id prefix");
+        assert!(uuid::Uuid::parse_str(uuid).is_ok());
+        let created = sighting["created"].as_str().expect("Missing created");
+        assert!(humantime::parse_rfc3339(created).is_ok());
+        assert!(to_sighting_template(&indicator, &[])
+            .get("where_sighted_refs")
+            .is_none());
+    }
+
+    #[test]
+    fn warning_header_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/taxii2/")
+            .with_header("Warning", r#"299 - "Deprecated endpoint""#)
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = mock_client(&server.url());
+        assert!(agent.warnings().is_empty());
+        agent.get_discovery().expect("Failed to get discovery");
+        assert_eq!(agent.warnings(), [r#"299 - "Deprecated endpoint""#]);
+        assert_eq!(agent.take_warnings().len(), 1);
+        assert!(agent.warnings().is_empty());
+        for _ in 0..=MAX_WARNINGS {
+            agent.get_discovery().expect("Failed to get discovery");
+        }
+        assert_eq!(agent.warnings().len(), MAX_WARNINGS);
+    }
+
+    #[test]
+    fn merge_into_test() {
+        let mut server = mockito::Server::new();
+        let objects = [
+            versioned_indicator_json("a", "2024-01-01T00:00:00.000Z"),
+            versioned_indicator_json("b", "2024-03-01T00:00:00.000Z"),
+            versioned_indicator_json("c", "2024-01-01T00:00:00.000Z"),
+            versioned_indicator_json("d", "2024-01-01T00:00:00.000Z"),
+        ];
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_of(&objects, None))
+            .create();
+        let agent = mock_client(&server.url());
+        let mut store: HashMap<String, CCIndicator> = [
+            ("a", "2024-01-01T00:00:00.000Z"),
+            ("b", "2024-02-01T00:00:00.000Z"),
+            ("c", "2024-02-01T00:00:00.000Z"),
+        ]
+        .iter()
+        .map(|(id, modified)| {
+            let indicator: CCIndicator =
+                serde_json::from_str(&versioned_indicator_json(id, modified))
+                    .expect("Failed to parse indicator");
+            ((*id).to_string(), indicator)
+        })
+        .collect();
+        let report = agent
+            .merge_into(&mut store, Some("c1"), false, None)
+            .expect("Failed to merge");
+        assert_eq!(
+            report,
+            MergeReport {
+                inserted: 1,
+                updated: 1,
+                unchanged: 2,
+            }
+        );
+        assert_eq!(store.len(), 4);
+        assert_eq!(store["b"].modified, "2024-03-01T00:00:00.000Z");
+        assert_eq!(store["c"].modified, "2024-02-01T00:00:00.000Z");
+    }
+
+    #[test]
+    fn serialize_omits_absent_fields_test() {
+        let mut indicator: CCIndicator =
+            serde_json::from_str(&indicator_json("indicator--a")).expect("Failed to parse");
+        indicator.description = None;
+        let value = serde_json::to_value(&indicator).expect("Failed to serialize");
+        assert!(value.get("description").is_none());
+        assert!(value.get("confidence").is_none());
+        assert_eq!(value["type"], "indicator");
+        indicator.confidence = Some(80);
+        let value = serde_json::to_value(&indicator).expect("Failed to serialize");
+        assert_eq!(value["confidence"], 80);
+    }
+
+    #[test]
+    fn validate_ids_test() {
+        let parse = |id: &str| -> CCIndicator {
+            serde_json::from_str(&indicator_json(id)).expect("Failed to parse indicator")
+        };
+        let indicators = [
+            parse("indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f"),
+            parse("indicator-8e2e2d2b"),
+            parse("Indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f"),
+            parse("indicator--8e2e2d2b17d44cbf938f98ee46b3cd3f"),
+        ];
+        let invalid: Vec<&str> = validate_ids(&indicators)
+            .iter()
+            .map(|indicator| indicator.id.as_str())
+            .collect();
+        assert_eq!(
+            invalid,
+            [
+                "indicator-8e2e2d2b",
+                "Indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+                "indicator--8e2e2d2b17d44cbf938f98ee46b3cd3f",
+            ]
+        );
+    }
+
+    #[test]
+    fn objects_of_types_test() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded(
+                    "match[type]".into(),
+                    "indicator,malware,attack-pattern".into(),
+                ),
+                mockito::Matcher::Regex("match\\[type\\]=indicator,malware,attack-pattern$".into()),
+            ]))
+            .with_body(envelope_of(
+                &[
+                    indicator_json("indicator--a"),
+                    r#"{"type":"malware","id":"malware--b","is_family":false}"#.to_string(),
+                ],
+                None,
+            ))
+            .create();
+        let agent = mock_client(&server.url());
+        let objects = agent
+            .get_cc_objects_of_types(
+                Some("c1"),
+                false,
+                &["indicator", "malware", "attack-pattern"],
+            )
+            .expect("Failed to get objects");
+        let types: Vec<&str> = objects
+            .iter()
+            .filter_map(|object| object["type"].as_str())
+            .collect();
+        assert_eq!(types, ["indicator", "malware"]);
+        mock.assert();
+    }
+
+    #[test]
+    fn collections_cache_test() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/api/collections/")
+            .with_body(COLLECTIONS_BODY)
+            .expect(2)
+            .create();
+        let agent = mock_client(&server.url()).with_collections_cache_ttl(Duration::from_secs(60));
+        let first = agent
+            .get_collections(Some("api"))
+            .expect("Failed to get collections");
+        let second = agent
+            .get_collections(Some("api"))
+            .expect("Failed to get cached collections");
+        assert_eq!(first, second);
+        agent.invalidate_collections_cache();
+        agent
+            .get_collections(Some("api"))
+            .expect("Failed to get collections after invalidation");
+        mock.assert();
+    }
+
+    #[test]
+    fn dump_collection_bundle_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .with_body(envelope_json(&["a", "b"], Some("p2")))
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(envelope_json(&["c"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let path = env::temp_dir().join(format!("bundle-{}.json", uuid::Uuid::new_v4()));
+        let count = agent
+            .dump_collection_bundle(Some("c1"), false, &path)
+            .expect("Failed to dump bundle");
+        let contents = std::fs::read_to_string(&path).expect("Failed to read bundle");
+        std::fs::remove_file(&path).expect("Failed to remove bundle");
+        let bundle: serde_json::Value =
+            serde_json::from_str(&contents).expect("Bundle is not valid JSON");
+        assert_eq!(count, 3);
+        assert_eq!(bundle["type"], "bundle");
+        assert!(bundle["id"]
+            .as_str()
+            .is_some_and(|id| id.starts_with("bundle--")));
+        let ids: Vec<&str> = bundle["objects"]
+            .as_array()
+            .expect("Bundle has no objects")
+            .iter()
+            .filter_map(|object| object["id"].as_str())
+            .collect();
+        assert_eq!(ids, ["a", "b", "c"]);
+        validate_bundle(&bundle).expect("Exported bundle is not valid");
+    }
+
+    #[test]
+    fn validate_bundle_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .with_body(envelope_json(&["a", "b"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let path = env::temp_dir().join(format!("bundle-{}.json", uuid::Uuid::new_v4()));
+        let count = agent
+            .dump_collection_bundle(Some("c1"), false, &path)
+            .expect("Failed to dump bundle");
+        let contents = std::fs::read_to_string(&path).expect("Failed to read bundle");
+        std::fs::remove_file(&path).expect("Failed to remove bundle");
+        let mut bundle: serde_json::Value =
+            serde_json::from_str(&contents).expect("Bundle is not valid JSON");
+        validate_bundle(&bundle).expect("Exported bundle is not valid");
+        assert_eq!(bundle["objects"].as_array().map(Vec::len), Some(count));
+
+        let mut corrupted = bundle.clone();
+        corrupted["type"] = "envelope".into();
+        assert!(validate_bundle(&corrupted).is_err());
+        let mut corrupted = bundle.clone();
+        corrupted["id"] = "bundle--not-a-uuid".into();
+        assert!(validate_bundle(&corrupted).is_err());
+        let mut corrupted = bundle.clone();
+        corrupted["objects"] = serde_json::json!({"type": "indicator"});
+        assert!(validate_bundle(&corrupted).is_err());
+        bundle["objects"][0]["type"] = serde_json::Value::Null;
+        assert!(validate_bundle(&bundle).is_err());
+    }
+
+    #[test]
+    fn single_object_envelope_test() {
+        let body = format!(r#"{{"more":false,"objects":{}}}"#, indicator_json("a"));
+        let envelope: CCEnvelope = serde_json::from_str(&body).expect("Failed to parse");
+        assert_eq!(envelope.objects.len(), 1);
+        assert_eq!(envelope.objects[0].id, "a");
+        let envelope: CCEnvelope =
+            serde_json::from_str(&envelope_json(&["a", "b"], None)).expect("Failed to parse");
+        assert_eq!(envelope.objects.len(), 2);
+    }
+
+    #[test]
+    fn default_added_after_test() {
+        let mut server = mockito::Server::new();
+        let default = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "added_after".into(),
+                "2024-01-01T00:00:00Z".into(),
+            ))
+            .with_body(envelope_json(&["a"], None))
+            .create();
+        let explicit = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "added_after".into(),
+                "2024-06-01T00:00:00Z".into(),
+            ))
+            .with_body(envelope_json(&["b"], None))
+            .create();
+        let agent = mock_client(&server.url()).with_default_added_after("2024-01-01T00:00:00Z");
+        agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, true)
+            .expect("Failed to get indicators");
+        agent
+            .get_cc_indicators(
+                Some("c1"),
+                None,
+                false,
+                Some("2024-06-01T00:00:00Z"),
+                &None,
+                true,
+            )
+            .expect("Failed to get indicators");
+        default.assert();
+        explicit.assert();
+
+        let mut server = mockito::Server::new();
+        let collection = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Regex("^limit=1000$".into()))
+            .with_body(envelope_json(&["a"], None))
+            .expect(2)
+            .create();
+        let manifest = server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::Regex("^limit=10000$".into()))
+            .with_body(r#"{"more":false,"objects":[]}"#)
+            .create();
+        let agent = mock_client(&server.url()).with_default_added_after("2024-01-01T00:00:00Z");
+        let path = env::temp_dir().join(format!("bundle-{}.json", uuid::Uuid::new_v4()));
+        agent
+            .dump_collection_bundle(Some("c1"), false, &path)
+            .expect("Failed to dump collection");
+        std::fs::remove_file(&path).expect("Failed to remove bundle");
+        agent
+            .snapshot_collection(Some("c1"), false)
+            .expect("Failed to snapshot collection");
+        agent
+            .estimate_collection_size(Some("c1"), false)
+            .expect("Failed to estimate collection size");
+        collection.assert();
+        manifest.assert();
+    }
+
+    #[test]
+    fn newest_indicators_test() {
+        let mut server = mockito::Server::new();
+        let created = |id: &str, created: &str| {
+            indicator_json(id).replace(
+                r#""created":"2024-01-01T00:00:00.000Z""#,
+                &format!(r#""created":"{created}""#),
+            )
+        };
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .with_body(envelope_of(
+                &[
+                    created("a", "2024-01-01T00:00:00Z"),
+                    created("b", "2024-04-01T00:00:00Z"),
+                ],
+                Some("p2"),
+            ))
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(envelope_of(
+                &[
+                    created("c", "2024-03-01T00:00:00Z"),
+                    created("d", "2024-02-01T00:00:00Z"),
+                ],
+                None,
+            ))
+            .create();
+        let agent = mock_client(&server.url());
+        let newest = agent
+            .get_newest_cc_indicators(Some("c1"), false, 2)
+            .expect("Failed to get newest indicators");
+        let ids: Vec<&str> = newest.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["b", "c"]);
+    }
+
+    #[test]
+    fn envelope_into_iter_test() {
+        let envelope: CCEnvelope =
+            serde_json::from_str(&envelope_json(&["a", "b"], None)).expect("Failed to parse");
+        let mut ids = Vec::new();
+        for indicator in envelope {
+            ids.push(indicator.id);
+        }
+        assert_eq!(ids, ["a", "b"]);
+    }
+
+    #[test]
+    fn has_new_since_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("limit".into(), "1".into()),
+                mockito::Matcher::UrlEncoded("added_after".into(), "2024-01-01T00:00:00Z".into()),
+            ]))
+            .with_body(
+                r#"{"more":true,"objects":[{"id":"a","date_added":"2024-02-01T00:00:00Z","version":"2024-02-01T00:00:00Z"}]}"#,
+            )
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "added_after".into(),
+                "2024-06-01T00:00:00Z".into(),
+            ))
+            .with_body("{}")
+            .create();
+        let agent = mock_client(&server.url());
+        assert!(agent
+            .has_new_since(Some("c1"), false, "2024-01-01T00:00:00Z")
+            .expect("Failed to check for new data"));
+        assert!(!agent
+            .has_new_since(Some("c1"), false, "2024-06-01T00:00:00Z")
+            .expect("Failed to check for new data"));
+    }
+
+    #[test]
+    fn partial_cancel_test() {
+        let mut server = mockito::Server::new();
+        let cancel = std::sync::Arc::new(AtomicBool::new(false));
+        let first_body = envelope_json(&["a", "b"], Some("p2"));
+        let flag = std::sync::Arc::clone(&cancel);
+        let first = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .with_chunked_body(move |writer| {
+                flag.store(true, atomic::Ordering::SeqCst);
+                writer.write_all(first_body.as_bytes())
+            })
+            .expect(1)
+            .create();
+        let second = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(envelope_json(&["c"], None))
+            .expect(0)
+            .create();
+        let agent = mock_client(&server.url()).with_cancel_token(cancel);
+        let pull = agent
+            .get_cc_indicators_partial(Some("c1"), None, false, None, &None, None)
+            .expect("Failed to get indicators");
+        assert!(pull.cancelled);
+        assert!(!pull.timed_out);
+        assert_eq!(pull.indicators.len(), 2);
+        assert_eq!(pull.next.as_deref(), Some("p2"));
+        first.assert();
+        second.assert();
+    }
+
+    #[test]
+    fn strict_deserialization_test() {
+        let mut server = mockito::Server::new();
+        let malformed = r#"{"id":"indicator--bad","type":"indicator"}"#.to_string();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_of(&[indicator_json("a"), malformed.clone()], None))
+            .create();
+        let agent = mock_client(&server.url()).with_strict_deserialization(5);
+        let error = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, true)
+            .expect_err("Malformed object was accepted");
+        let JsonDeserializationError(message) = *error else {
+            panic!("Unexpected error: {error:?}");
+        };
+        assert!(message.contains("indicator--bad"));
+        assert!(message.contains(&malformed));
+        assert!(!message.contains("\"id\":\"a\""));
+    }
+
+    #[test]
+    fn after_watermark_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::UrlEncoded("match[id]".into(), "b".into()))
+            .with_body(
+                r#"{"objects":[{"id":"b","date_added":"2024-02-01T00:00:00Z","version":"2024-02-01T00:00:00Z"}]}"#,
+            )
+            .create();
+        let after = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "added_after".into(),
+                "2024-02-01T00:00:00Z".into(),
+            ))
+            .with_body(envelope_json(&["c", "d"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let indicators = agent
+            .get_cc_indicators_after_watermark(Some("c1"), false, Some("b"))
+            .expect("Failed to continue from watermark");
+        let ids: Vec<&str> = indicators.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["c", "d"]);
+        after.assert();
+
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .with_body(envelope_json(&["a", "b", "c", "d"], None))
+            .create();
+        let indicators = agent
+            .get_cc_indicators_after_watermark(Some("c1"), false, None)
+            .expect("Failed to pull without watermark");
+        assert_eq!(indicators.len(), 4);
+    }
+
+    #[test]
+    fn missing_locally_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(
+                r#"{"objects":[
+                    {"id":"a","date_added":"2024-01-01T00:00:00Z","version":"2024-01-01T00:00:00Z"},
+                    {"id":"b","date_added":"2024-01-01T00:00:00Z","version":"2024-01-01T00:00:00Z"},
+                    {"id":"c","date_added":"2024-01-01T00:00:00Z","version":"2024-01-01T00:00:00Z"},
+                    {"id":"c","date_added":"2024-02-01T00:00:00Z","version":"2024-02-01T00:00:00Z"}
+                ]}"#,
+            )
+            .create();
+        let agent = mock_client(&server.url());
+        let local: HashSet<String> = ["a", "z"].iter().map(ToString::to_string).collect();
+        let missing = agent
+            .missing_locally(Some("c1"), false, &local)
+            .expect("Failed to compare with manifest");
+        assert_eq!(missing, ["b", "c"]);
+    }
+
+    #[test]
+    fn next_cursor_encoding_test() {
+        let mut server = mockito::Server::new();
+        let cursor = "a+b/c==%2F";
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .with_body(envelope_json(&["a"], Some(cursor)))
+            .create();
+        let second = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("next".into(), cursor.into()),
+                mockito::Matcher::Regex("next=a%2Bb%2Fc%3D%3D%252F$".into()),
+            ]))
+            .with_body(envelope_json(&["b"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let indicators = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, true)
+            .expect("Failed to follow encoded cursor");
+        assert_eq!(indicators.len(), 2);
+        second.assert();
+    }
+
+    #[test]
+    fn metrics_test() {
+        #[derive(Default)]
+        struct Counts {
+            requests: std::sync::atomic::AtomicUsize,
+            errors: Mutex<Vec<String>>,
+            bytes: std::sync::atomic::AtomicU64,
+        }
+
+        struct Recorder(std::sync::Arc<Counts>);
+
+        impl Metrics for Recorder {
+            fn on_request(&self, _method: &str, _url: &str) {
+                self.0.requests.fetch_add(1, atomic::Ordering::SeqCst);
+            }
+
+            fn on_error(&self, error: &crate::TaxiiError) {
+                let variant = match error {
+                    crate::TaxiiError::TaxiiNotFound(_) => "not found",
+                    _ => "other",
+                };
+                self.0
+                    .errors
+                    .lock()
+                    .expect("Poisoned lock")
+                    .push(variant.to_string());
+            }
+
+            fn on_bytes(&self, bytes: u64) {
+                self.0.bytes.fetch_add(bytes, atomic::Ordering::SeqCst);
             }
         }
-        Ok(all_indicators)
+
+        let mut server = mockito::Server::new();
+        let first = envelope_json(&["a"], Some("p2"));
+        let second = envelope_json(&["b"], None);
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .with_body(&first)
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(&second)
+            .create();
+        server
+            .mock("GET", "/api/collections/missing/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .create();
+        let counts = std::sync::Arc::new(Counts::default());
+        let agent =
+            mock_client(&server.url()).with_metrics(Recorder(std::sync::Arc::clone(&counts)));
+        agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, true)
+            .expect("Failed to get indicators");
+        assert!(agent
+            .get_cc_indicators(Some("missing"), None, false, None, &None, true)
+            .is_err());
+        assert_eq!(counts.requests.load(atomic::Ordering::SeqCst), 3);
+        assert_eq!(*counts.errors.lock().expect("Poisoned lock"), ["not found"]);
+        assert_eq!(
+            counts.bytes.load(atomic::Ordering::SeqCst),
+            (first.len() + second.len()) as u64
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use dotenv;
-    use std::env;
+    #[test]
+    fn dump_collection_by_type_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_of(
+                &[
+                    indicator_json("indicator--a"),
+                    r#"{"type":"malware","id":"malware--b","is_family":false}"#.to_string(),
+                    indicator_json("indicator--c"),
+                ],
+                None,
+            ))
+            .create();
+        let agent = mock_client(&server.url());
+        let dir = env::temp_dir().join(format!("dump-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir(&dir).expect("Failed to create dump directory");
+        let counts = agent
+            .dump_collection_by_type(Some("c1"), false, &dir)
+            .expect("Failed to dump by type");
+        let lines = |name: &str| {
+            std::fs::read_to_string(dir.join(name))
+                .expect("Failed to read dump file")
+                .lines()
+                .map(|line| serde_json::from_str::<serde_json::Value>(line).is_ok())
+                .filter(|valid| *valid)
+                .count()
+        };
+        let (indicators, malware) = (lines("indicator.ndjson"), lines("malware.ndjson"));
+        std::fs::remove_dir_all(&dir).expect("Failed to remove dump directory");
+        assert_eq!(counts.len(), 2);
+        assert_eq!(counts["indicator"], 2);
+        assert_eq!(counts["malware"], 1);
+        assert_eq!(indicators, 2);
+        assert_eq!(malware, 1);
+    }
 
     #[test]
-    fn get_discovery_test() {
-        dotenv::dotenv().ok();
-        let username = env::var("TAXII_USERNAME").expect("You've not set the TAXII_USERNAME");
-        let api_key = env::var("TAXII_API_KEY").expect("You've not set the TAXII_API_KEY");
-        let agent = CCTaxiiClient::new(&username, &api_key);
-        let discovery = agent.get_discovery().expect("Failed to get discovery");
+    fn default_collection_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(COLLECTIONS_BODY.replace(
+                r#"[{"can_read""#,
+                r#"[{"can_read":true,"can_write":false,"id":"c9","media_types":["application/stix+json;version=2.1"],"name":"later","title":"Later collection"},{"can_read":true,"can_write":false,"id":"c0","media_types":["application/stix+json;version=2.0"],"name":"stix-2.0","title":"STIX 2.0 collection"},{"can_read""#,
+            ))
+            .create();
+        let objects = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_json(&["a"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        assert_eq!(
+            agent
+                .get_collections(None)
+                .expect("Failed to get collections"),
+            ["c9", "c0", "c1"]
+        );
+        agent
+            .get_cc_indicators(None, None, false, None, &None, true)
+            .expect("Failed to get indicators from the default collection");
+        objects.assert();
+    }
 
-        assert_eq!(discovery.api_roots, ["/api/"], "API roots are incorrect");
+    #[test]
+    fn streaming_large_object_test() {
+        let mut server = mockito::Server::new();
+        let description = "x".repeat(1024 * 1024);
+        let body = envelope_of(
+            &[indicator_json("a").replace("Mock indicator", &description)],
+            None,
+        );
+        let digest = format!(
+            "sha-512=:{}:",
+            base64::encode(&Sha512::digest(body.as_bytes()))
+        );
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_header("Content-Digest", &digest)
+            .with_chunked_body(move |writer| {
+                body.as_bytes()
+                    .chunks(64 * 1024)
+                    .try_for_each(|chunk| writer.write_all(chunk))
+            })
+            .create();
+        for verify in [false, true] {
+            let agent = mock_client(&server.url()).with_verify_digest(verify);
+            let indicators = agent
+                .get_cc_indicators(Some("c1"), None, false, None, &None, true)
+                .expect("Failed to stream large object");
+            assert_eq!(indicators.len(), 1);
+            assert_eq!(
+                indicators[0].description.as_deref().map(str::len),
+                Some(description.len())
+            );
+        }
+    }
+
+    #[test]
+    fn client_name_test() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/taxii2/")
+            .match_header("x-taxii-client-name", "acme-soar")
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = mock_client(&server.url())
+            .with_client_name("old-name")
+            .with_client_name("acme-soar");
+        agent.get_discovery().expect("Failed to get discovery");
+        mock.assert();
+        let names = agent
+            .common_headers()
+            .into_iter()
+            .filter(|(key, _)| *key == "X-TAXII-Client-Name")
+            .count();
+        assert_eq!(names, 1);
+    }
+
+    #[test]
+    fn indicators_by_pattern_type_test() {
+        let mut server = mockito::Server::new();
+        let sigma = indicator_json("indicator--2")
+            .replace(r#""pattern_type":"stix""#, r#""pattern_type":"sigma""#);
+        let objects = [
+            indicator_json("indicator--1"),
+            sigma,
+            indicator_json("indicator--3"),
+        ];
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_of(&objects, None))
+            .create();
+        let agent = mock_client(&server.url());
+        let grouped = agent
+            .get_cc_indicators_by_pattern_type(Some("c1"), false)
+            .expect("Failed to get indicators");
+        assert_eq!(grouped.len(), 2);
+        let stix: Vec<&str> = grouped["stix"].iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(stix, ["indicator--1", "indicator--3"]);
+        assert_eq!(grouped["sigma"].len(), 1);
+        assert_eq!(grouped["sigma"][0].id, "indicator--2");
+    }
+
+    #[test]
+    fn base_url_test() {
+        let agent = CCTaxiiClient::new("user", "key")
+            .with_base_url("https://taxii.example.com:8443/proxy//")
+            .expect("Valid base URL rejected");
+        assert_eq!(agent.base_url, "https://taxii.example.com:8443/proxy");
+        let agent = CCTaxiiClient::new("user", "key")
+            .with_base_url("http://[::1]:8080")
+            .expect("Valid base URL rejected");
+        assert_eq!(agent.base_url, "http://[::1]:8080");
+        for url in [
+            "htp://typo",
+            "https://",
+            "https:///api",
+            "https://bad host",
+            "https://taxii.example.com:port",
+            "https://taxii.example.com/?x=1",
+            "https://[::1",
+        ] {
+            let result = CCTaxiiClient::new("user", "key").with_base_url(url);
+            assert!(
+                matches!(result.map(|_| ()).map_err(|e| *e), Err(InvalidBaseUrl(_))),
+                "{url} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn indicator_accessors_test() {
+        let indicator: CCIndicator = serde_json::from_str(&indicator_json("indicator--1"))
+            .expect("Failed to parse indicator");
+        assert_eq!(indicator.id(), "indicator--1");
+        assert_eq!(indicator.r#type(), "indicator");
+        assert_eq!(indicator.pattern(), "[ipv4-addr:value = '198.51.100.1']");
+        assert_eq!(indicator.pattern_type(), "stix");
+        assert_eq!(indicator.created(), "2024-01-01T00:00:00.000Z");
+        assert_eq!(indicator.valid_from(), "2024-01-01T00:00:00Z");
+        assert_eq!(indicator.description(), Some("Mock indicator"));
+        assert_eq!(indicator.confidence(), None);
+    }
+
+    #[test]
+    fn wait_for_status_test() {
+        const PENDING: &str = r#"{"id":"status-1","status":"pending","total_count":2,"success_count":1,"failure_count":0,"pending_count":1}"#;
+        const COMPLETE: &str = r#"{"id":"status-1","status":"complete","total_count":2,"success_count":2,"failure_count":0,"pending_count":0}"#;
+        let mut server = mockito::Server::new();
+        let pending = server
+            .mock("GET", "/api/status/status-1/")
+            .with_body(PENDING)
+            .expect(2)
+            .create();
+        let complete = server
+            .mock("GET", "/api/status/status-1/")
+            .with_body(COMPLETE)
+            .create();
+        let agent = mock_client(&server.url());
+        let status = agent
+            .wait_for_status(
+                "status-1",
+                false,
+                Duration::from_millis(10),
+                Duration::from_secs(5),
+            )
+            .expect("Failed to wait for status");
+        assert_eq!(status.status, "complete");
+        assert_eq!(status.success_count, 2);
+        pending.assert();
+        complete.assert();
+
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/status/status-1/")
+            .with_body(PENDING)
+            .create();
+        let agent = mock_client(&server.url());
+        let result = agent.wait_for_status(
+            "status-1",
+            false,
+            Duration::from_millis(10),
+            Duration::from_millis(50),
+        );
+        assert!(matches!(result.map_err(|e| *e), Err(Timeout(_))));
+    }
+
+    #[test]
+    fn poll_status_until_complete_test() {
+        const PENDING: &str = r#"{"id":"status-1","status":"pending","total_count":1,"success_count":0,"failure_count":0,"pending_count":1}"#;
+        const COMPLETE: &str = r#"{"id":"status-1","status":"complete","total_count":1,"success_count":1,"failure_count":0,"pending_count":0}"#;
+        let mut server = mockito::Server::new();
+        let pending = server
+            .mock("GET", "/feeds/status/status-1/")
+            .with_body(PENDING)
+            .expect(1)
+            .create();
+        let complete = server
+            .mock("GET", "/feeds/status/status-1/")
+            .with_body(COMPLETE)
+            .create();
+        let agent = mock_client(&server.url());
+        let status = agent
+            .get_status_at("feeds", "status-1")
+            .expect("Failed to get status");
+        assert_eq!(status.status, "pending");
+        let status = agent
+            .poll_status_until_complete(
+                "feeds",
+                "status-1",
+                Duration::from_millis(10),
+                Duration::from_secs(5),
+            )
+            .expect("Failed to poll status");
+        assert_eq!(status.success_count, 1);
+        pending.assert();
+        complete.assert();
+    }
+
+    #[test]
+    fn build_objects_url_test() {
+        let base = "api/collections/c1/objects/";
         assert_eq!(
-            discovery.contact, "it.support@cloudcover.net",
-            "Contact email is incorrect"
+            build_objects_url("api", "c1", 1000, None, &None, None),
+            format!("{base}?limit=1000")
         );
-        assert_eq!(discovery.default, "/api/", "Default is incorrect");
-        assert_eq!(discovery.description, "This API ROOT contains TAXII 2.1 REST API endpoints that serve CloudCover STIX 2.1 data", "Description is incorrect");
         assert_eq!(
-            discovery.title, "CloudCover TAXII Server",
-            "Title is incorrect"
+            build_objects_url("api", "c1", 5, Some("2024-01-01T00:00:00Z"), &None, None),
+            format!("{base}?limit=5&added_after=2024-01-01T00:00:00Z")
+        );
+        assert_eq!(
+            build_objects_url("api", "c1", 5, None, &None, Some("a+b/c==")),
+            format!("{base}?limit=5&next=a%2Bb%2Fc%3D%3D")
+        );
+        let matches = Some(HashMap::from([("type", "indicator"), ("id", "x")]));
+        assert_eq!(
+            build_objects_url("acct", "c2", 5, None, &matches, None),
+            "acct/collections/c2/objects/?limit=5&match[id]=x&match[type]=indicator"
+        );
+        assert_eq!(
+            build_objects_url(
+                "api",
+                "c1",
+                5,
+                Some("2024-01-01T00:00:00Z"),
+                &matches,
+                Some("p2")
+            ),
+            format!(
+                "{base}?limit=5&added_after=2024-01-01T00:00:00Z&match[id]=x&match[type]=indicator&next=p2"
+            )
+        );
+        assert_eq!(
+            build_objects_url("api", "c1", 5, None, &Some(HashMap::new()), None),
+            format!("{base}?limit=5")
         );
     }
 
     #[test]
-    fn get_collections_test() {
-        dotenv::dotenv().ok();
-        let username = env::var("TAXII_USERNAME").expect("You've not set the TAXII_USERNAME");
-        let api_key = env::var("TAXII_API_KEY").expect("You've not set the TAXII_API_KEY");
-        let agent = CCTaxiiClient::new(&username, &api_key);
+    fn pagination_url_test() {
+        let mut server = mockito::Server::new();
+        let first = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1".into()))
+            .with_body(envelope_json(&["indicator--1"], Some("p2")))
+            .create();
+        let second = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1&next=p2".into()))
+            .with_body(envelope_json(&["indicator--2"], Some("p3")))
+            .create();
+        let third = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1&next=p3".into()))
+            .with_body(envelope_json(&["indicator--3"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let indicators = agent
+            .get_cc_indicators(Some("c1"), Some(1), false, None, &None, true)
+            .expect("Failed to get indicators");
+        assert_eq!(indicators.len(), 3);
+        first.assert();
+        second.assert();
+        third.assert();
+    }
+
+    #[test]
+    fn get_object_test() {
+        let mut server = mockito::Server::new();
+        let objects = [
+            versioned_indicator_json("indicator--1", "2024-01-01T00:00:00Z"),
+            versioned_indicator_json("indicator--1", "2024-02-01T00:00:00Z"),
+        ];
+        server
+            .mock("GET", "/api/collections/c1/objects/indicator--1/")
+            .with_body(envelope_of(&objects, None))
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/indicator--2/")
+            .with_status(404)
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/indicator--3/")
+            .with_body(r#"{"objects":[{"id":"indicator--3"}]}"#)
+            .create();
+        let agent = mock_client(&server.url());
+        let indicator = agent
+            .get_object("c1", "indicator--1", false)
+            .expect("Failed to get object");
+        assert_eq!(indicator.modified, "2024-02-01T00:00:00Z");
+        let missing = agent.get_object("c1", "indicator--2", false);
+        assert!(matches!(
+            missing.map_err(|e| *e),
+            Err(crate::TaxiiError::TaxiiNotFound(_))
+        ));
+        let malformed = agent.get_object("c1", "indicator--3", false);
+        assert!(matches!(
+            malformed.map_err(|e| *e),
+            Err(JsonDeserializationError(_))
+        ));
+    }
+
+    #[test]
+    fn get_manifest_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::Exact(
+                "limit=1000&added_after=2024-01-01T00:00:00Z".into(),
+            ))
+            .with_body(
+                r#"{"more":true,"next":"p2","objects":[{"id":"indicator--1","date_added":"2024-01-02T00:00:00Z","version":"2024-01-02T00:00:00Z","media_type":"application/stix+json;version=2.1"}]}"#,
+            )
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::Exact(
+                "limit=1000&added_after=2024-01-01T00:00:00Z&next=p2".into(),
+            ))
+            .with_body(
+                r#"{"objects":[{"id":"indicator--2","date_added":"2024-01-03T00:00:00Z","version":"2024-01-03T00:00:00Z"}]}"#,
+            )
+            .create();
+        let agent = mock_client(&server.url());
+        let records = agent
+            .get_manifest(Some("c1"), false, Some("2024-01-01T00:00:00Z"))
+            .expect("Failed to get manifest");
+        let ids: Vec<&str> = records.iter().map(|record| record.id.as_str()).collect();
+        assert_eq!(ids, ["indicator--1", "indicator--2"]);
+        assert_eq!(records[1].date_added, "2024-01-03T00:00:00Z");
+        assert!(records[1].media_type.is_none());
+    }
+
+    #[test]
+    fn get_api_root_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/")
+            .with_body(
+                r#"{"title":"Public root","versions":["application/taxii+json;version=2.1"],"max_content_length":104857600}"#,
+            )
+            .create();
+        let agent = mock_client(&server.url());
+        let api_root = agent.get_api_root("/api/").expect("Failed to get API root");
+        assert_eq!(api_root.title, "Public root");
+        assert!(api_root.description.is_none());
+        assert_eq!(api_root.versions, ["application/taxii+json;version=2.1"]);
+        assert_eq!(api_root.max_content_length, 104_857_600);
+    }
+
+    #[test]
+    fn indicators_by_ids_chunking_test() {
+        let mut server = mockito::Server::new();
+        let url = server.url();
+        let lengths = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let recorded = std::sync::Arc::clone(&lengths);
+        let mock = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_request(move |request| {
+                let full = format!("{url}{}", request.path_and_query());
+                recorded.lock().expect("Poisoned lock").push(full.len());
+                let query = request.path_and_query().rsplit("match[id]=").next();
+                let ids: Vec<&str> = query.unwrap_or_default().split(',').collect();
+                envelope_json(&ids, None).into_bytes()
+            })
+            .expect(3)
+            .create();
+        let ids: Vec<String> = (0..30).map(|i| format!("indicator--{i:04}")).collect();
+        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let agent = mock_client(&server.url()).with_max_url_length(250);
+        let indicators = agent
+            .get_cc_indicators_by_ids(Some("c1"), false, &ids)
+            .expect("Failed to get indicators");
+        mock.assert();
+        let returned: Vec<&str> = indicators.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(returned, ids);
+        let lengths = lengths.lock().expect("Poisoned lock");
+        assert!(lengths.iter().all(|length| *length <= 250), "{lengths:?}");
+    }
+
+    #[test]
+    fn collections_detailed_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(COLLECTIONS_BODY)
+            .create();
+        let agent = mock_client(&server.url());
         let collections = agent
-            .get_collections(Some("api"))
+            .get_collections_detailed("api")
             .expect("Failed to get collections");
         assert_eq!(collections.len(), 1);
+        assert_eq!(collections[0].id, "c1");
+        assert_eq!(collections[0].title, "Mock collection");
+        assert!(collections[0].can_read);
+        assert!(!collections[0].can_write);
     }
 
+    #[cfg(feature = "chrono")]
     #[test]
-    fn get_indicators_test() {
-        dotenv::dotenv().ok();
-        let username = env::var("TAXII_USERNAME").expect("You've not set the TAXII_USERNAME");
-        let api_key = env::var("TAXII_API_KEY").expect("You've not set the TAXII_API_KEY");
-        let agent = CCTaxiiClient::new(&username, &api_key);
+    fn filter_valid_from_between_test() {
+        let indicator = |id: &str, valid_from: &str| -> CCIndicator {
+            let json = indicator_json(id).replace(
+                r#""valid_from":"2024-01-01T00:00:00Z""#,
+                &format!(r#""valid_from":"{valid_from}""#),
+            );
+            serde_json::from_str(&json).expect("Failed to parse indicator")
+        };
+        let indicators = vec![
+            indicator("before", "2024-01-31T23:59:59.999Z"),
+            indicator("start", "2024-02-01T00:00:00Z"),
+            indicator("offset", "2024-02-15T01:00:00+01:00"),
+            indicator("last", "2024-02-29T23:59:59Z"),
+            indicator("end", "2024-03-01T00:00:00Z"),
+            indicator("invalid", "yesterday"),
+        ];
+        let start = DateTime::parse_from_rfc3339("2024-02-01T00:00:00Z")
+            .expect("Invalid start")
+            .with_timezone(&Utc);
+        let end = DateTime::parse_from_rfc3339("2024-03-01T00:00:00Z")
+            .expect("Invalid end")
+            .with_timezone(&Utc);
+        let kept: Vec<String> = filter_valid_from_between(indicators, start, end)
+            .into_iter()
+            .map(|indicator| indicator.id)
+            .collect();
+        assert_eq!(kept, ["start", "offset", "last"]);
+    }
+
+    #[test]
+    fn timeout_test() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let url = format!(
+            "http://{}",
+            listener.local_addr().expect("No local address")
+        );
+        let agent = mock_client(&url).with_timeout(Duration::from_millis(100));
+        let started = Instant::now();
+        let result = agent.get_discovery();
+        assert!(matches!(result.map_err(|e| *e), Err(Timeout(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(listener);
+    }
+
+    #[test]
+    fn indicators_capped_test() {
+        let mut server = mockito::Server::new();
+        let pages = [
+            (
+                "limit=2",
+                envelope_json(&["indicator--1", "indicator--2"], Some("p2")),
+            ),
+            (
+                "limit=2&next=p2",
+                envelope_json(&["indicator--3", "indicator--4"], Some("p3")),
+            ),
+            (
+                "limit=2&next=p3",
+                envelope_json(&["indicator--5", "indicator--6"], Some("p4")),
+            ),
+        ];
+        let mocks: Vec<mockito::Mock> = pages
+            .iter()
+            .map(|(query, body)| {
+                server
+                    .mock("GET", "/api/collections/c1/objects/")
+                    .match_query(mockito::Matcher::Exact((*query).into()))
+                    .with_body(body)
+                    .create()
+            })
+            .collect();
+        let agent = mock_client(&server.url());
         let indicators = agent
-            .get_cc_indicators(None, Some(5), false, None, &None, false)
-            .expect("Failed to get objects");
-        assert_eq!(indicators.len(), 5);
+            .get_cc_indicators_capped(Some("c1"), 2, 5, false, None, &None)
+            .expect("Failed to get indicators");
+        let ids: Vec<&str> = indicators.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "indicator--1",
+                "indicator--2",
+                "indicator--3",
+                "indicator--4",
+                "indicator--5"
+            ]
+        );
+        for mock in &mocks {
+            mock.assert();
+        }
+        assert!(agent
+            .get_cc_indicators_capped(Some("c1"), 2, 0, false, None, &None)
+            .expect("Failed to get indicators")
+            .is_empty());
+    }
+
+    #[test]
+    fn indicator_to_map_test() {
+        let indicator: CCIndicator = serde_json::from_str(&indicator_json("indicator--1"))
+            .expect("Failed to parse indicator");
+        let map = indicator.to_map();
+        assert_eq!(map.len(), 11);
+        assert_eq!(map["id"], "indicator--1");
+        assert_eq!(map["type"], "indicator");
+        assert_eq!(map["pattern"], "[ipv4-addr:value = '198.51.100.1']");
+        assert_eq!(map["description"], "Mock indicator");
+        assert_eq!(map["valid_from"], "2024-01-01T00:00:00Z");
+        assert!(!map.contains_key("confidence"));
+    }
+
+    #[test]
+    fn retry_test() {
+        let mut server = mockito::Server::new();
+        let unavailable = server
+            .mock("GET", "/taxii2/")
+            .with_status(503)
+            .expect(2)
+            .create();
+        let discovery = server
+            .mock("GET", "/taxii2/")
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = mock_client(&server.url()).with_retry(3, Duration::from_millis(1));
+        agent.get_discovery().expect("Failed to get discovery");
+        unavailable.assert();
+        discovery.assert();
+
+        let mut server = mockito::Server::new();
+        let unavailable = server
+            .mock("GET", "/taxii2/")
+            .with_status(503)
+            .expect(2)
+            .create();
+        let missing = server
+            .mock("GET", "/api/collections/")
+            .with_status(404)
+            .expect(1)
+            .create();
+        let agent = mock_client(&server.url()).with_retry(2, Duration::from_millis(1));
+        let result = agent.get_discovery();
+        assert!(matches!(
+            result.map_err(|e| *e),
+            Err(TaxiiError::TaxiiServerError(response)) if response.status() == 503
+        ));
+        assert!(agent.get_collections(None).is_err());
+        unavailable.assert();
+        missing.assert();
+    }
+
+    #[test]
+    fn backoff_test() {
+        let base = Duration::from_millis(100);
+        for _ in 0..20 {
+            let first = backoff(base, 1);
+            assert!(first >= Duration::from_millis(50) && first <= base);
+            let third = backoff(base, 3);
+            assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+        }
+    }
+
+    #[test]
+    fn clock_skew_test() {
+        let mut server = mockito::Server::new();
+        let server_time = SystemTime::now() + Duration::from_secs(3600);
+        server
+            .mock("GET", "/taxii2/")
+            .with_header("Date", &httpdate::fmt_http_date(server_time))
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = mock_client(&server.url());
+        assert!(agent.clock_skew().is_none());
+        agent.get_discovery().expect("Failed to get discovery");
+        let skew = agent.clock_skew().expect("No skew measured");
+        assert!((3590..=3600).contains(&skew), "{skew}");
+    }
+
+    #[test]
+    fn rate_limited_test() {
+        let mut server = mockito::Server::new();
+        let limited = server
+            .mock("GET", "/taxii2/")
+            .with_status(429)
+            .with_header("Retry-After", "1")
+            .expect(1)
+            .create();
+        let discovery = server
+            .mock("GET", "/taxii2/")
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = mock_client(&server.url()).with_retry(3, Duration::from_millis(1));
+        let started = Instant::now();
+        agent.get_discovery().expect("Failed to get discovery");
+        assert!(started.elapsed() >= Duration::from_secs(1));
+        limited.assert();
+        discovery.assert();
+
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/taxii2/")
+            .with_status(429)
+            .with_header("Retry-After", "120")
+            .create();
+        let agent = mock_client(&server.url());
+        let result = agent.get_discovery().map_err(|e| *e);
+        let Err(TaxiiError::TaxiiRateLimited(response)) = result else {
+            panic!("Rate limit not reported");
+        };
+        assert_eq!(response.header("Retry-After"), Some("120"));
+
+        let mut server = mockito::Server::new();
+        let limited = server
+            .mock("GET", "/taxii2/")
+            .with_status(429)
+            .with_header("Retry-After", "86400")
+            .expect(1)
+            .create();
+        let agent = mock_client(&server.url())
+            .with_retry(3, Duration::from_millis(1))
+            .with_max_retry_after(Duration::from_secs(5));
+        let started = Instant::now();
+        assert!(matches!(
+            agent.get_discovery().map_err(|e| *e),
+            Err(TaxiiError::TaxiiRateLimited(_))
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        limited.assert();
+    }
+
+    #[test]
+    fn parse_retry_after_test() {
+        assert_eq!(parse_retry_after(" 30 "), Some(Duration::from_secs(30)));
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        let later = SystemTime::now() + Duration::from_secs(90);
+        let delay =
+            parse_retry_after(&httpdate::fmt_http_date(later)).expect("HTTP date not parsed");
+        assert!(delay > Duration::from_secs(85) && delay <= Duration::from_secs(90));
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+
+    #[test]
+    fn snapshot_collection_test() {
+        let mut server = mockito::Server::new();
+        let malware = r#"{"type":"malware","id":"malware--1","name":"m1"}"#.to_string();
+        let report = r#"{"type":"report","id":"report--1","name":"r1"}"#.to_string();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".into()))
+            .with_body(envelope_of(
+                &[indicator_json("indicator--1"), malware],
+                Some("p2"),
+            ))
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000&next=p2".into()))
+            .with_body(envelope_of(&[indicator_json("indicator--2"), report], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let snapshot = agent
+            .snapshot_collection(Some("c1"), false)
+            .expect("Failed to snapshot collection");
+        assert_eq!(snapshot.len(), 3);
+        let indicators: Vec<&str> = snapshot["indicator"]
+            .iter()
+            .filter_map(|object| object["id"].as_str())
+            .collect();
+        assert_eq!(indicators, ["indicator--1", "indicator--2"]);
+        assert_eq!(snapshot["malware"][0]["name"], "m1");
+        assert_eq!(snapshot["report"].len(), 1);
+    }
+
+    #[test]
+    fn iter_indicators_test() {
+        let mut server = mockito::Server::new();
+        let first = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=2".into()))
+            .with_body(envelope_json(&["indicator--1", "indicator--2"], Some("p2")))
+            .create();
+        let second = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=2&next=p2".into()))
+            .with_body(envelope_json(&["indicator--3"], Some("p3")))
+            .create();
+        let failing = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=2&next=p3".into()))
+            .with_status(500)
+            .create();
+        let agent = mock_client(&server.url());
+        let mut indicators = agent.iter_cc_indicators(Some("c1"), Some(2), false, None, &None);
+        let first_id = indicators.next().map(|i| i.map(|i| i.id));
+        assert_eq!(
+            first_id.and_then(Result::ok).as_deref(),
+            Some("indicator--1")
+        );
+        first.assert();
+        assert!(!second.matched());
+        let rest: Vec<Result<CCIndicator>> = indicators.collect();
+        assert_eq!(rest.len(), 3);
+        assert_eq!(
+            rest[1].as_ref().map(|i| i.id.as_str()).ok(),
+            Some("indicator--3")
+        );
+        assert!(rest[2].is_err());
+        second.assert();
+        failing.assert();
+
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(r#"{"collections":[]}"#)
+            .create();
+        let agent = mock_client(&server.url());
+        let results: Vec<Result<CCIndicator>> = agent
+            .iter_cc_indicators(None, None, false, None, &None)
+            .collect();
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+
+    #[test]
+    fn retry_policy_test() {
+        struct Attempts(atomic::AtomicUsize);
+
+        impl Metrics for std::sync::Arc<Attempts> {
+            fn on_request(&self, _method: &str, _url: &str) {
+                self.0.fetch_add(1, atomic::Ordering::SeqCst);
+            }
+        }
+
+        let policy = RetryPolicy {
+            retry_on_5xx: false,
+            ..RetryPolicy::default()
+        };
+        let mut server = mockito::Server::new();
+        let unavailable = server
+            .mock("GET", "/taxii2/")
+            .with_status(503)
+            .expect(1)
+            .create();
+        let agent = mock_client(&server.url())
+            .with_retry(3, Duration::from_millis(1))
+            .with_retry_policy(policy);
+        assert!(agent.get_discovery().is_err());
+        unavailable.assert();
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").expect("Failed to bind");
+        let url = format!(
+            "http://{}",
+            listener.local_addr().expect("No local address")
+        );
+        let attempts = std::sync::Arc::new(Attempts(atomic::AtomicUsize::new(0)));
+        let agent = mock_client(&url)
+            .with_timeout(Duration::from_millis(50))
+            .with_retry(3, Duration::from_millis(1))
+            .with_retry_policy(policy)
+            .with_metrics(std::sync::Arc::clone(&attempts));
+        assert!(matches!(
+            agent.get_discovery().map_err(|e| *e),
+            Err(Timeout(_))
+        ));
+        assert_eq!(attempts.0.load(atomic::Ordering::SeqCst), 3);
+        drop(listener);
+
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(r#"{"collections":[{"can_read":true,"can_write":true,"id":"c1","media_types":["application/stix+json;version=2.1"],"name":"writable","title":"Writable"}]}"#)
+            .create();
+        let post = server
+            .mock("POST", "/api/collections/c1/objects/")
+            .with_status(503)
+            .expect(1)
+            .create();
+        let agent = mock_client(&server.url()).with_retry(3, Duration::from_millis(1));
+        let objects = [serde_json::json!({"type": "indicator", "id": "indicator--1"})];
+        assert!(matches!(
+            agent.add_objects("c1", &objects, false).map_err(|e| *e),
+            Err(TaxiiError::TaxiiServerError(_))
+        ));
+        post.assert();
+    }
+
+    #[test]
+    fn pagination_filters_test() {
+        let mut server = mockito::Server::new();
+        let base = "limit=2&added_after=2024-01-01T00:00:00Z&match[type]=indicator";
+        let pages = [
+            (
+                base.to_string(),
+                envelope_json(&["indicator--1"], Some("p2")),
+            ),
+            (
+                format!("{base}&next=p2"),
+                envelope_json(&["indicator--2"], Some("p3")),
+            ),
+            (
+                format!("{base}&next=p3"),
+                envelope_json(&["indicator--3"], None),
+            ),
+        ];
+        let mocks: Vec<mockito::Mock> = pages
+            .into_iter()
+            .map(|(query, body)| {
+                server
+                    .mock("GET", "/api/collections/c1/objects/")
+                    .match_query(mockito::Matcher::Exact(query))
+                    .with_body(body)
+                    .expect(1)
+                    .create()
+            })
+            .collect();
+        let agent = mock_client(&server.url());
+        let matches = Some(HashMap::from([("type", "indicator")]));
+        let indicators = agent
+            .get_cc_indicators(
+                Some("c1"),
+                Some(2),
+                false,
+                Some("2024-01-01T00:00:00Z"),
+                &matches,
+                true,
+            )
+            .expect("Failed to get indicators");
+        let ids: Vec<&str> = indicators.iter().map(|i| i.id.as_str()).collect();
+        assert_eq!(ids, ["indicator--1", "indicator--2", "indicator--3"]);
+        for mock in &mocks {
+            mock.assert();
+        }
+    }
+
+    #[test]
+    fn version_filter_test() {
+        assert_eq!(
+            VersionFilter::parse("first").ok(),
+            Some(VersionFilter::First)
+        );
+        assert_eq!(VersionFilter::parse("all").ok(), Some(VersionFilter::All));
+        let timestamp = "2024-01-01T00:00:00.000Z";
+        assert_eq!(
+            VersionFilter::parse(timestamp).map(|v| v.as_str()).ok(),
+            Some(timestamp)
+        );
+        assert!(VersionFilter::parse("latest").is_err());
+        assert_eq!(VersionFilter::Last.as_str(), "last");
+
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::AllOf(vec![
+                mockito::Matcher::UrlEncoded("match[version]".into(), "first,all".into()),
+                mockito::Matcher::UrlEncoded("match[spec_version]".into(), "2.1".into()),
+            ]))
+            .with_body(envelope_json(&["a"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let matches = HashMap::from([("version", "first,all"), ("spec_version", "2.1")]);
+        let indicators = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &Some(matches), true)
+            .expect("Failed to get indicators");
+        assert_eq!(indicators.len(), 1);
+        mock.assert();
+
+        for (key, value) in [
+            ("version", "latest"),
+            ("spec_version", "2"),
+            ("spec_version", "v2.1"),
+        ] {
+            let matches = HashMap::from([(key, value)]);
+            let result =
+                agent.get_cc_indicators(Some("c1"), None, false, None, &Some(matches), true);
+            assert!(matches!(result.map_err(|e| *e), Err(InvalidFilter(_))));
+        }
+    }
+
+    #[test]
+    fn add_objects_test() {
+        const WRITABLE: &str = r#"{"collections":[{"can_read":true,"can_write":true,"id":"c2","media_types":["application/stix+json;version=2.1"],"name":"writable","title":"Writable"},{"can_read":true,"can_write":false,"id":"c1","media_types":["application/stix+json;version=2.1"],"name":"read-only","title":"Read-only"},{"can_read":true,"can_write":true,"id":"c4","media_types":["application/stix+json;version=2.0"],"name":"stix-2.0","title":"STIX 2.0"}]}"#;
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(WRITABLE)
+            .expect(4)
+            .create();
+        let post = server
+            .mock("POST", "/api/collections/c2/objects/")
+            .match_header("Content-Type", "application/taxii+json;version=2.1")
+            .match_body(mockito::Matcher::Json(serde_json::json!({
+                "objects": [{"type": "indicator", "id": "indicator--1"}]
+            })))
+            .with_status(202)
+            .with_body(r#"{"id":"status-1","status":"pending","total_count":1,"success_count":0,"failure_count":0,"pending_count":1}"#)
+            .expect(1)
+            .create();
+        let agent = mock_client(&server.url());
+        let objects = [serde_json::json!({"type": "indicator", "id": "indicator--1"})];
+        let status = agent
+            .add_objects("c2", &objects, false)
+            .expect("Failed to add objects");
+        assert_eq!(status.id, "status-1");
+        assert_eq!(status.pending_count, 1);
+        for collection in ["c1", "c3", "c4"] {
+            let result = agent.add_objects(collection, &objects, false);
+            assert!(matches!(
+                result.map_err(|e| *e),
+                Err(TaxiiCollectionError(_))
+            ));
+        }
+        post.assert();
+    }
+
+    #[test]
+    fn iter_cc_indicators_by_valid_from_test() {
+        fn page(valid_from: &[(&str, &str)], next: Option<&str>) -> String {
+            let objects: Vec<String> = valid_from
+                .iter()
+                .map(|(id, timestamp)| {
+                    indicator_json(id).replace(
+                        r#""valid_from":"2024-01-01T00:00:00Z""#,
+                        &format!(r#""valid_from":"{timestamp}""#),
+                    )
+                })
+                .collect();
+            envelope_of(&objects, next)
+        }
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact(
+                "limit=3&added_after=2024-01-01T00:00:00Z".to_string(),
+            ))
+            .with_body(page(
+                &[
+                    ("c", "2024-03-01T00:00:00Z"),
+                    ("a", "2024-01-01T00:00:00.5Z"),
+                    ("b", "2024-02-01T00:00:00Z"),
+                ],
+                Some("p2"),
+            ))
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(page(
+                &[
+                    ("e", "2024-05-01T00:00:00Z"),
+                    ("d", "2024-04-01T00:00:00Z"),
+                    ("f", "2024-04-01T00:00:00Z"),
+                ],
+                None,
+            ))
+            .create();
+        let agent = mock_client(&server.url());
+        let ids: Vec<String> = agent
+            .iter_cc_indicators_by_valid_from(
+                Some("c1"),
+                Some(3),
+                false,
+                Some("2024-01-01T00:00:00Z"),
+                &None,
+                3,
+            )
+            .map(|indicator| indicator.map(|i| i.id))
+            .collect::<Result<_>>()
+            .expect("Failed to iterate indicators");
+        assert_eq!(ids, ["a", "b", "c", "d", "f", "e"]);
+    }
+
+    #[test]
+    fn overview_test() {
+        let mut server = mockito::Server::new();
+        let discovery = server
+            .mock("GET", "/taxii2/")
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let collections = server
+            .mock("GET", "/user/collections/")
+            .with_body(COLLECTIONS_BODY)
+            .create();
+        let agent = mock_client(&server.url());
+        let overview = agent.overview(true).expect("Failed to get overview");
+        assert_eq!(overview.discovery.title, "CloudCover TAXII Server");
+        assert_eq!(overview.collections.len(), 1);
+        assert_eq!(overview.collections[0].id, "c1");
+        discovery.assert();
+        collections.assert();
+    }
+
+    #[test]
+    fn get_object_versions_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/user/collections/c1/objects/indicator--1/versions/")
+            .with_body(
+                r#"{"more":false,"versions":["2024-01-01T00:00:00.000Z","2024-02-01T00:00:00.000Z"]}"#,
+            )
+            .create();
+        server
+            .mock("GET", "/user/collections/c1/objects/indicator--2/versions/")
+            .with_body(r#"{"more":false}"#)
+            .create();
+        let agent = mock_client(&server.url());
+        let versions = agent
+            .get_object_versions("c1", "indicator--1", true)
+            .expect("Failed to get versions");
+        assert_eq!(
+            versions,
+            ["2024-01-01T00:00:00.000Z", "2024-02-01T00:00:00.000Z"]
+        );
+        let versions = agent
+            .get_object_versions("c1", "indicator--2", true)
+            .expect("Failed to get versions");
+        assert!(versions.is_empty());
+    }
+
+    #[test]
+    fn delete_object_test() {
+        let mut server = mockito::Server::new();
+        let all = server
+            .mock("DELETE", "/user/collections/c1/objects/indicator--1/")
+            .match_query(mockito::Matcher::Missing)
+            .with_status(200)
+            .create();
+        let versioned = server
+            .mock("DELETE", "/user/collections/c1/objects/indicator--1/")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "match[version]".into(),
+                "2024-01-01T00:00:00.000Z".into(),
+            ))
+            .with_status(200)
+            .create();
+        let offset = server
+            .mock("DELETE", "/user/collections/c1/objects/indicator--1/")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "match[version]".into(),
+                "2024-01-01T00:00:00+01:00".into(),
+            ))
+            .with_status(200)
+            .create();
+        server
+            .mock("DELETE", "/user/collections/c1/objects/indicator--2/")
+            .with_status(404)
+            .create();
+        let agent = mock_client(&server.url());
+        agent
+            .delete_object("c1", "indicator--1", None, true)
+            .expect("Failed to delete object");
+        agent
+            .delete_object("c1", "indicator--1", Some("2024-01-01T00:00:00.000Z"), true)
+            .expect("Failed to delete version");
+        agent
+            .delete_object(
+                "c1",
+                "indicator--1",
+                Some("2024-01-01T00:00:00+01:00"),
+                true,
+            )
+            .expect("Failed to delete version with offset");
+        let missing = agent.delete_object("c1", "indicator--2", None, true);
+        assert!(matches!(
+            missing.map_err(|e| *e),
+            Err(crate::TaxiiError::TaxiiNotFound(_))
+        ));
+        let invalid = agent.delete_object("c1", "indicator--1", Some("latest"), true);
+        assert!(matches!(invalid.map_err(|e| *e), Err(InvalidFilter(_))));
+        all.assert();
+        versioned.assert();
+        offset.assert();
+    }
+
+    #[test]
+    fn estimate_collection_size_test() {
+        let mut server = mockito::Server::new();
+        let records = |ids: &[&str]| {
+            ids.iter()
+                .map(|id| {
+                    format!(
+                        r#"{{"id":"{id}","date_added":"2024-01-01T00:00:00Z","version":"2024-01-01T00:00:00Z"}}"#
+                    )
+                })
+                .collect::<Vec<String>>()
+                .join(",")
+        };
+        server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::Exact("limit=10000".to_string()))
+            .with_body(format!(
+                r#"{{"more":true,"next":"p2","objects":[{}]}}"#,
+                records(&["a", "b"])
+            ))
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/manifest/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(format!(
+                r#"{{"more":false,"objects":[{}]}}"#,
+                records(&["c"])
+            ))
+            .create();
+        let agent = mock_client(&server.url());
+        let size = agent
+            .estimate_collection_size(Some("c1"), false)
+            .expect("Failed to estimate size");
+        assert_eq!(size, 3);
+    }
+
+    #[test]
+    fn get_cc_indicators_page_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=2".to_string()))
+            .with_body(envelope_json(&["a", "b"], Some("p 2")))
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=2&next=p%202".to_string()))
+            .with_body(envelope_json(&["c"], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let first = agent
+            .get_cc_indicators_page(Some("c1"), Some(2), false, None, &None, None)
+            .expect("Failed to get first page");
+        assert!(first.more());
+        assert_eq!(first.next(), Some("p 2"));
+        assert_eq!(first.objects().len(), 2);
+        let second = agent
+            .get_cc_indicators_page(Some("c1"), Some(2), false, None, &None, first.next())
+            .expect("Failed to get second page");
+        assert!(!second.more());
+        assert_eq!(second.next(), None);
+        assert_eq!(second.objects()[0].id, "c");
+    }
+
+    #[test]
+    fn stream_cc_indicators_csv_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Exact("limit=1000".to_string()))
+            .with_body(envelope_json(&["a", "b"], Some("p2")))
+            .create();
+        let mut quoted = indicator_json("c").replace("Mock indicator", r#"Says \"hi\", twice"#);
+        quoted = quoted.replace(r#""name":"c""#, r#""name":"c","labels":["x","y"]"#);
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(envelope_of(&[quoted], None))
+            .create();
+        let agent = mock_client(&server.url());
+        let mut buffer = Vec::new();
+        let rows = agent
+            .stream_cc_indicators_csv(Some("c1"), false, None, &None, &mut buffer)
+            .expect("Failed to stream CSV");
+        assert_eq!(rows, 3);
+        let csv = String::from_utf8(buffer).expect("CSV is not UTF-8");
+        let lines: Vec<&str> = csv.split_terminator("\r\n").collect();
+        assert_eq!(lines.len(), 4);
+        assert_eq!(lines[0], CSV_HEADER.trim_end());
+        assert!(lines[1].starts_with("a,indicator,a,[ipv4-addr:value = '198.51.100.1'],stix,"));
+        assert!(lines[3].ends_with(r#",,"Says ""hi"", twice",x;y"#));
+    }
+
+    #[test]
+    fn unexpected_content_type_test() {
+        let mut server = mockito::Server::new();
+        server
+            .mock("GET", "/taxii2/")
+            .with_header("Content-Type", "text/html; charset=utf-8")
+            .with_body("<html><body>Sign in to the guest network</body></html>")
+            .create();
+        server
+            .mock("GET", "/api/collections/")
+            .with_header("Content-Type", "application/taxii+json;version=2.1")
+            .with_body(COLLECTIONS_BODY)
+            .create();
+        let agent = mock_client(&server.url());
+        let discovery = agent.get_discovery();
+        assert!(matches!(
+            discovery.map_err(|e| *e),
+            Err(UnexpectedContentType { got }) if got == "text/html; charset=utf-8"
+        ));
+        let collections = agent
+            .get_collections_detailed("api")
+            .expect("Failed to get collections");
+        assert_eq!(collections.len(), 1);
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn timestamps_test() {
+        let indicator: CCIndicator =
+            serde_json::from_str(&indicator_json("a")).expect("Failed to parse indicator");
+        let created = indicator.created_at().expect("Invalid created");
+        assert_eq!(taxii_timestamp(created), "2024-01-01T00:00:00.000Z");
+        assert_eq!(indicator.valid_from_at().ok(), Some(created));
+        assert!(indicator.modified_at().is_ok());
+        let mut malformed = indicator;
+        malformed.modified = "2024-01-01".to_string();
+        assert!(malformed.modified_at().is_err());
+    }
+
+    #[test]
+    fn added_after_validation_test() {
+        for valid in [
+            "2024-01-01T00:00:00Z",
+            "2024-02-29T23:59:60.123456z",
+            "2024-01-01t01:00:00.5+01:00",
+            "2023-12-31T19:00:00-05:00",
+        ] {
+            assert!(is_rfc3339(valid), "{valid}");
+        }
+        for invalid in [
+            "2024-01-01",
+            "2023-02-29T00:00:00Z",
+            "2024-01-01T24:00:00Z",
+            "2024-01-01T00:00:00",
+            "2024-01-01T00:00:00.Z",
+            "2024-01-01T00:00:00+0100",
+            "2024-01-01T00:00:00+01:00Z",
+            "2024-13-01T00:00:00Z",
+        ] {
+            assert!(!is_rfc3339(invalid), "{invalid}");
+        }
+
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_json(&["a"], None))
+            .expect(1)
+            .create();
+        let agent = mock_client(&server.url());
+        agent
+            .get_cc_indicators(
+                Some("c1"),
+                None,
+                false,
+                Some("2024-01-01T00:00:00.000Z"),
+                &None,
+                true,
+            )
+            .expect("Failed to get indicators");
+        let result =
+            agent.get_cc_indicators(Some("c1"), None, false, Some("2024-01-01"), &None, true);
+        assert!(matches!(result.map_err(|e| *e), Err(InvalidFilter(_))));
+        mock.assert();
+    }
+
+    #[test]
+    fn status_error_variants_test() {
+        let mut server = mockito::Server::new();
+        for status in [400, 403, 429, 500] {
+            server
+                .mock("GET", format!("/status-{status}/").as_str())
+                .with_status(status)
+                .create();
+        }
+        let agent = mock_client(&server.url());
+        let error = |status: u16| agent.request(&format!("status-{status}/")).err();
+        assert!(matches!(
+            error(400).as_deref(),
+            Some(TaxiiError::TaxiiBadRequest(_))
+        ));
+        assert!(matches!(
+            error(403).as_deref(),
+            Some(TaxiiError::TaxiiGenericError(_))
+        ));
+        assert!(matches!(
+            error(429).as_deref(),
+            Some(TaxiiError::TaxiiRateLimited(_))
+        ));
+        assert!(matches!(
+            error(500).as_deref(),
+            Some(TaxiiError::TaxiiServerError(_))
+        ));
+    }
+
+    #[test]
+    fn builder_test() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/taxii2/")
+            .match_header("user-agent", "acme-soar/1.0")
+            .match_header("x-proxy-token", "secret")
+            .match_header("accept", "application/taxii+json")
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = CCTaxiiClient::builder("user", "key")
+            .base_url(server.url())
+            .timeout(Duration::from_secs(5))
+            .retry(3, Duration::ZERO)
+            .retry_policy(RetryPolicy {
+                retry_on_429: false,
+                ..RetryPolicy::default()
+            })
+            .user_agent("acme-soar/1.0")
+            .extra_header("X-Proxy-Token", "secret")
+            .extra_header("accept", "application/taxii+json")
+            .build()
+            .expect("Failed to build client");
+        assert_eq!(agent.timeout, Duration::from_secs(5));
+        assert_eq!(agent.retry_attempts, 3);
+        assert!(!agent.retry_policy.retry_on_429);
+        agent.get_discovery().expect("Failed to get discovery");
+        mock.assert();
+        let result = CCTaxiiClient::builder("user", "key")
+            .base_url("htp://typo")
+            .build();
+        assert!(matches!(
+            result.map(|_| ()).map_err(|e| *e),
+            Err(InvalidBaseUrl(_))
+        ));
+    }
+
+    #[test]
+    fn bearer_token_test() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/taxii2/")
+            .match_header("authorization", "Bearer my_token")
+            .with_body(DISCOVERY_BODY)
+            .create();
+        let agent = CCTaxiiClient::with_bearer_token("my_token", None)
+            .with_base_url(server.url())
+            .expect("Invalid base URL");
+        agent.get_discovery().expect("Failed to get discovery");
+        mock.assert();
+        assert_eq!(agent.private_root(), None);
+        assert!(agent
+            .common_headers()
+            .contains(&("Authorization", "[REDACTED]")));
+        let agent = CCTaxiiClient::with_bearer_token("my_token", Some("my_account"));
+        assert_eq!(agent.private_root(), Some("my_account"));
+    }
+
+    #[test]
+    fn bearer_token_without_account_test() {
+        let mut server = mockito::Server::new();
+        let mock = server.mock("GET", mockito::Matcher::Any).expect(0).create();
+        let agent = CCTaxiiClient::with_bearer_token("my_token", None)
+            .with_base_url(server.url())
+            .expect("Invalid base URL");
+        let result = agent.get_cc_indicators(Some("c1"), None, true, None, &None, false);
+        assert!(matches!(
+            result.map_err(|e| *e),
+            Err(TaxiiCollectionError(message)) if message == "No private API root configured"
+        ));
+        assert!(agent.get_status("s1", true).is_err());
+        mock.assert();
+    }
+
+    #[test]
+    fn get_all_indicators_test() {
+        let mut server = mockito::Server::new();
+        let collections: Vec<String> = ["c1", "c2", "c3"]
+            .iter()
+            .map(|id| {
+                format!(
+                    r#"{{"can_read":true,"can_write":false,"id":"{id}","media_types":["application/stix+json;version=2.1"],"name":"{id}","title":"{id}"}}"#
+                )
+            })
+            .collect();
+        server
+            .mock("GET", "/api/collections/")
+            .with_body(format!(r#"{{"collections":[{}]}}"#, collections.join(",")))
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_json(&["indicator--1"], Some("p2")))
+            .expect(1)
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(envelope_json(&["indicator--2"], None))
+            .create();
+        server
+            .mock("GET", "/api/collections/c2/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body(envelope_json(&["indicator--3"], None))
+            .create();
+        server
+            .mock("GET", "/api/collections/c3/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_status(404)
+            .create();
+        let agent = mock_client(&server.url()).with_parallelism(2);
+        let results = agent
+            .get_all_indicators("api", None)
+            .expect("Failed to list collections");
+        assert_eq!(results.len(), 3);
+        let ids = |collection: &str| -> Vec<String> {
+            results[collection]
+                .as_ref()
+                .map(|indicators| indicators.iter().map(|i| i.id.clone()).collect())
+                .unwrap_or_default()
+        };
+        assert_eq!(ids("c1"), ["indicator--1", "indicator--2"]);
+        assert_eq!(ids("c2"), ["indicator--3"]);
+        assert!(matches!(
+            results["c3"].as_ref().map_err(|e| &**e),
+            Err(TaxiiError::TaxiiNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn dedup_test() {
+        let mut server = mockito::Server::new();
+        let first = [
+            versioned_indicator_json("indicator--1", "2024-01-01T00:00:00.000Z"),
+            versioned_indicator_json("indicator--2", "2024-03-01T00:00:00.000Z"),
+        ];
+        let second = [
+            versioned_indicator_json("indicator--2", "2024-02-01T00:00:00.000Z"),
+            versioned_indicator_json("indicator--1", "2024-01-01T00:00:00.5Z"),
+            versioned_indicator_json("indicator--3", "2024-01-01T00:00:00.000Z"),
+        ];
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Regex("^limit=1000$".into()))
+            .with_body(envelope_of(&first, Some("p2")))
+            .expect(2)
+            .create();
+        server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded("next".into(), "p2".into()))
+            .with_body(envelope_of(&second, None))
+            .expect(2)
+            .create();
+        let agent = mock_client(&server.url());
+        let all = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, true)
+            .expect("Failed to get indicators");
+        assert_eq!(all.len(), 5);
+        let agent = agent.with_dedup(true);
+        let latest = agent
+            .get_cc_indicators(Some("c1"), None, false, None, &None, true)
+            .expect("Failed to get indicators");
+        let versions: Vec<(&str, &str)> = latest
+            .iter()
+            .map(|i| (i.id.as_str(), i.modified.as_str()))
+            .collect();
+        assert_eq!(
+            versions,
+            [
+                ("indicator--1", "2024-01-01T00:00:00.5Z"),
+                ("indicator--2", "2024-03-01T00:00:00.000Z"),
+                ("indicator--3", "2024-01-01T00:00:00.000Z"),
+            ]
+        );
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn indicators_since_test() {
+        let mut server = mockito::Server::new();
+        let mock = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::UrlEncoded(
+                "added_after".into(),
+                "2024-01-01T12:30:00.000Z".into(),
+            ))
+            .with_body(envelope_json(&["indicator--1"], None))
+            .create();
+        let since = DateTime::parse_from_rfc3339("2024-01-01T13:30:00+01:00")
+            .expect("Invalid timestamp")
+            .with_timezone(&Utc);
+        let indicators = mock_client(&server.url())
+            .get_cc_indicators_since(Some("c1"), None, false, since, &None, true)
+            .expect("Failed to get indicators");
+        assert_eq!(indicators.len(), 1);
+        mock.assert();
+    }
+
+    #[test]
+    fn new_with_base_url_test() {
+        let agent = CCTaxiiClient::new_with_base_url("user", "key", "https://taxii.example.com/")
+            .expect("Valid base URL rejected");
+        assert_eq!(agent.base_url, "https://taxii.example.com");
+        assert_eq!(agent.private_root(), Some("user"));
+        assert_eq!(CCTaxiiClient::new("user", "key").base_url, DEFAULT_BASE_URL);
+        let result = CCTaxiiClient::new_with_base_url("user", "key", String::from("htp://typo"));
+        assert!(matches!(
+            result.map(|_| ()).map_err(|e| *e),
+            Err(InvalidBaseUrl(_))
+        ));
+    }
+
+    #[test]
+    fn cancel_token_test() {
+        let mut server = mockito::Server::new();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        let first = server
+            .mock("GET", "/api/collections/c1/objects/")
+            .match_query(mockito::Matcher::Any)
+            .with_body_from_request(move |_| {
+                flag.store(true, atomic::Ordering::SeqCst);
+                envelope_json(&["a"], Some("p2")).into_bytes()
+            })
+            .expect(1)
+            .create();
+        let agent = mock_client(&server.url()).with_cancel_token(Arc::clone(&cancel));
+        let result = agent.get_cc_indicators(Some("c1"), None, false, None, &None, true);
+        assert!(matches!(result.map_err(|e| *e), Err(Cancelled(_))));
+        let mut indicators = agent.iter_cc_indicators(Some("c1"), None, false, None, &None);
+        assert!(matches!(
+            indicators.next().and_then(Result::err).as_deref(),
+            Some(Cancelled(_))
+        ));
+        let pull = agent
+            .get_cc_indicators_partial(Some("c1"), None, false, None, &None, None)
+            .expect("Failed to get partial indicators");
+        assert!(pull.cancelled);
+        first.assert();
     }
 }
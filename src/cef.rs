@@ -0,0 +1,111 @@
+use crate::{pattern::indicator_observables, CCIndicator};
+
+/// Converts indicators to CEF (Common Event Format) lines, one per observable, for SIEMs
+/// such as `ArcSight`. Available with the `cef` feature.
+///
+/// Observables are extracted as by [`extract_observables`](crate::extract_observables), so an
+/// indicator whose pattern combines several comparisons yields several lines. Each observable is sent
+/// in the CEF extension matching its type:
+///
+/// - `ipv4-addr:value` and `ipv6-addr:value` as `src`.
+/// - `domain-name:value` as `dhost`.
+/// - `url:value` as `request`.
+/// - `file:hashes.*` as `fileHash`.
+/// - `file:name` as `fname`.
+///
+/// Observables of other types are skipped. The signature ID is the indicator's STIX id,
+/// and the severity is its `confidence` divided by 10, or 5 if it has none.
+///
+/// # Examples
+///
+/// ```
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+/// let indicators = agent.get_cc_indicators(None, None, false, None, &None, true)?;
+/// for line in to_cef(&indicators) {
+///     syslog.send(&line)?;
+/// }
+/// ```
+#[must_use]
+pub fn to_cef(indicators: &[CCIndicator]) -> Vec<String> {
+    indicators
+        .iter()
+        .flat_map(|indicator| {
+            indicator_observables(indicator)
+                .into_iter()
+                .filter_map(move |observable| {
+                    let key = extension_key(&observable.kind)?;
+                    Some(cef_line(indicator, key, &observable.value))
+                })
+        })
+        .collect()
+}
+
+/// Returns the CEF extension key carrying observables compared against `object_path`.
+fn extension_key(object_path: &str) -> Option<&'static str> {
+    match object_path {
+        "ipv4-addr:value" | "ipv6-addr:value" => Some("src"),
+        "domain-name:value" => Some("dhost"),
+        "url:value" => Some("request"),
+        "file:name" => Some("fname"),
+        path if path.starts_with("file:hashes.") => Some("fileHash"),
+        _ => None,
+    }
+}
+
+/// Builds the CEF line reporting one observable of `indicator`.
+fn cef_line(indicator: &CCIndicator, key: &str, value: &str) -> String {
+    let severity = indicator.confidence.map_or(5, |confidence| confidence / 10);
+    format!(
+        "CEF:0|CloudCover|{}|{}|{}|{}|{severity}|{key}={}",
+        env!("CARGO_PKG_NAME"),
+        env!("CARGO_PKG_VERSION"),
+        escape_header(&indicator.id),
+        escape_header(&indicator.name),
+        escape_extension(value),
+    )
+}
+
+/// Escapes a CEF header field, in which `\` and `|` are special.
+fn escape_header(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('|', "\\|")
+}
+
+/// Escapes a CEF extension value, in which `\`, `=`, and line breaks are special.
+fn escape_extension(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('=', "\\=")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn indicator(id: &str, pattern: &str) -> CCIndicator {
+        let mut indicator = crate::fixtures::indicator(id, pattern);
+        indicator.confidence = Some(80);
+        indicator.name = "Bad|actor".to_string();
+        indicator
+    }
+
+    #[test]
+    fn to_cef_test() {
+        let indicators = [
+            indicator("indicator--1", "[ipv4-addr:value = '198.51.100.1']"),
+            indicator(
+                "indicator--2",
+                "[domain-name:value = 'a=b.example.com'] OR [file:hashes.'SHA-256' = 'abcd']",
+            ),
+            indicator("indicator--3", "[process:name = 'evil.exe']"),
+            indicator("indicator--4", "[ipv4-addr:value != '198.51.100.2']"),
+        ];
+        let lines = to_cef(&indicators);
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("CEF:0|CloudCover|cc-taxii2-client-rs|"));
+        assert!(lines[0].ends_with("|indicator--1|Bad\\|actor|8|src=198.51.100.1"));
+        assert!(lines[1].ends_with("|dhost=a\\=b.example.com"));
+        assert!(lines[2].ends_with("|fileHash=abcd"));
+    }
+}
@@ -1,3 +1,5 @@
+use serde::Deserialize;
+use std::fmt;
 use ureq::Response;
 
 /// A specialized `Result` type for operations in the TAXII client.
@@ -19,17 +21,35 @@ pub enum TaxiiError {
 
     /// An authorization error occurred. This usually means that the credentials
     /// provided were incorrect or insufficient for the requested operation.
-    /// Contains the server's response for further inspection.
-    TaxiiAuthorizationError(Response),
+    /// Contains the server's response, with its
+    /// TAXII error message if it sent one.
+    TaxiiAuthorizationError(HttpError),
 
     /// The requested resource was not found on the TAXII server.
-    /// Contains the server's response for further inspection.
-    TaxiiNotFound(Response),
+    /// Contains the server's response, with its
+    /// TAXII error message if it sent one.
+    TaxiiNotFound(HttpError),
+
+    /// The server rejected the request with 429 (Too Many Requests), and no retry policy was
+    /// set or its attempts were exhausted. Contains the server's response, whose
+    /// `Retry-After` header tells how long to wait.
+    TaxiiRateLimited(HttpError),
+
+    /// The server rejected the request with 400 (Bad Request), for example because of a
+    /// malformed filter. Contains the server's response, with its
+    /// TAXII error message if it sent one.
+    TaxiiBadRequest(HttpError),
+
+    /// The server failed to handle the request and answered with a 5xx status code.
+    /// Contains the server's response, with its
+    /// TAXII error message if it sent one.
+    TaxiiServerError(HttpError),
 
     /// A generic error occurred. Used for various error conditions that do not
     /// fall under more specific categories.
-    /// Contains the server's response for further inspection.
-    TaxiiGenericError(Response),
+    /// Contains the server's response, with its
+    /// TAXII error message if it sent one.
+    TaxiiGenericError(HttpError),
 
     /// A error occured while trying to fetch collection IDs for a specified api root.
     TaxiiCollectionError(String),
@@ -37,4 +57,239 @@ pub enum TaxiiError {
     /// An error occurred while deserializing JSON data from the TAXII server.
     /// Contains a message describing the error.
     JsonDeserializationError(String),
+
+    /// The response body did not match the digest advertised by the server in its
+    /// `Content-Digest` header. Contains a message describing the mismatch.
+    IntegrityError(String),
+
+    /// An error occurred while reading or writing a local file.
+    /// Contains the underlying I/O error.
+    IoError(std::io::Error),
+
+    /// The configured base URL is not a valid `http` or `https` URL.
+    /// Contains a message describing the problem.
+    InvalidBaseUrl(String),
+
+    /// A filter has a value the TAXII specification does not define, such as
+    /// `match[version]=latest` or an `added_after` that is not an RFC 3339 timestamp.
+    /// Contains a message describing the value.
+    InvalidFilter(String),
+
+    /// An operation did not finish within its time limit.
+    /// Contains a message describing what was being waited for.
+    Timeout(String),
+
+    /// A pull was stopped because the client's cancel token was set.
+    /// Contains a message describing what was cancelled.
+    Cancelled(String),
+
+    /// The server answered discovery or a collections request with a successful status but
+    /// a body that is not JSON, such as the HTML page of a captive portal.
+    /// Contains the `Content-Type` the server sent.
+    UnexpectedContentType { got: String },
+}
+
+/// An HTTP error response from a TAXII server, read in full when the error occurred.
+///
+/// The body is kept, so the server's explanation is not lost once the connection is gone.
+/// If the body is a TAXII `error-message` resource, it is also available parsed through
+/// [`message`](Self::message).
+#[derive(Debug, Clone)]
+pub struct HttpError {
+    status: u16,
+    status_text: String,
+    url: String,
+    headers: Vec<(String, String)>,
+    body: String,
+    message: Option<TaxiiErrorMessage>,
+}
+
+impl HttpError {
+    /// Reads an error response, parsing its body as a TAXII `error-message` when possible.
+    pub(crate) fn from_response(response: Response) -> Self {
+        let status = response.status();
+        let status_text = response.status_text().to_string();
+        let url = response.get_url().to_string();
+        let headers = response
+            .headers_names()
+            .into_iter()
+            .filter_map(|name| {
+                let value = response.header(&name)?.to_string();
+                Some((name, value))
+            })
+            .collect();
+        let body = response.into_string().unwrap_or_default();
+        let message = serde_json::from_str(&body).ok();
+        Self {
+            status,
+            status_text,
+            url,
+            headers,
+            body,
+            message,
+        }
+    }
+
+    /// Returns the HTTP status code (e.g., 404).
+    #[must_use]
+    pub const fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Returns the reason phrase of the status line (e.g., `Not Found`).
+    #[must_use]
+    pub fn status_text(&self) -> &str {
+        &self.status_text
+    }
+
+    /// Returns the URL of the request that failed.
+    #[must_use]
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the value of the header `name`, ignoring case, if the server sent it.
+    #[must_use]
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Returns the raw response body, empty if it could not be read as text.
+    #[must_use]
+    pub fn body(&self) -> &str {
+        &self.body
+    }
+
+    /// Returns the TAXII error message the server sent, if the body is one.
+    #[must_use]
+    pub const fn message(&self) -> Option<&TaxiiErrorMessage> {
+        self.message.as_ref()
+    }
+}
+
+/// A TAXII `error-message` resource, explaining why a server rejected a request.
+///
+/// # Fields
+///
+/// - `title`: A short human-readable summary of the error.
+/// - `description`: A longer human-readable description, if provided.
+/// - `error_id`: An identifier for this occurrence of the error, if provided.
+/// - `error_code`: The server's code for this kind of error, if provided.
+/// - `http_status`: The HTTP status code, as a string, if provided.
+/// - `external_details`: A URL with more details about the error, if provided.
+/// - `details`: Other server-defined details, if provided.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TaxiiErrorMessage {
+    pub title: String,
+    pub description: Option<String>,
+    pub error_id: Option<String>,
+    pub error_code: Option<String>,
+    pub http_status: Option<String>,
+    pub external_details: Option<String>,
+    pub details: Option<serde_json::Value>,
+}
+
+impl fmt::Display for TaxiiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TaxiiConnectionError(message) => write!(f, "connection failed: {message}"),
+            Self::TaxiiAuthorizationError(response) => {
+                write_response(f, "authorization failed", response)
+            }
+            Self::TaxiiNotFound(response) => write_response(f, "resource not found", response),
+            Self::TaxiiRateLimited(response) => write_response(f, "rate limited", response),
+            Self::TaxiiBadRequest(response) => write_response(f, "bad request", response),
+            Self::TaxiiServerError(response) => write_response(f, "server error", response),
+            Self::TaxiiGenericError(response) => write_response(f, "request failed", response),
+            Self::TaxiiCollectionError(message) => write!(f, "collection error: {message}"),
+            Self::JsonDeserializationError(message) => {
+                write!(f, "failed to deserialize response: {message}")
+            }
+            Self::IntegrityError(message) => write!(f, "integrity check failed: {message}"),
+            Self::IoError(error) => write!(f, "I/O error: {error}"),
+            Self::InvalidBaseUrl(message) => write!(f, "invalid base URL: {message}"),
+            Self::InvalidFilter(message) => write!(f, "invalid filter: {message}"),
+            Self::Timeout(message) => write!(f, "timed out: {message}"),
+            Self::Cancelled(message) => write!(f, "cancelled: {message}"),
+            Self::UnexpectedContentType { got } => {
+                write!(f, "unexpected content type: expected JSON, got {got}")
+            }
+        }
+    }
+}
+
+/// Writes `label` followed by the status line and URL of an error response, and the TAXII
+/// error message if the server sent one.
+fn write_response(f: &mut fmt::Formatter<'_>, label: &str, error: &HttpError) -> fmt::Result {
+    write!(
+        f,
+        "{label}: HTTP {} {} from {}",
+        error.status(),
+        error.status_text(),
+        error.url()
+    )?;
+    if let Some(message) = error.message() {
+        write!(f, ": {}", message.title)?;
+        if let Some(description) = &message.description {
+            write!(f, " ({description})")?;
+        }
+    }
+    Ok(())
+}
+
+impl std::error::Error for TaxiiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_test() {
+        let response = Response::new(404, "Not Found", "").expect("Failed to build response");
+        let error = TaxiiError::TaxiiNotFound(HttpError::from_response(response));
+        assert_eq!(
+            error.to_string(),
+            "resource not found: HTTP 404 Not Found from https://example.com/"
+        );
+        let error: Box<dyn std::error::Error> =
+            Box::new(TaxiiError::IoError(std::io::ErrorKind::NotFound.into()));
+        assert!(error.to_string().starts_with("I/O error: "));
+        assert!(error.source().is_some());
+        let error = TaxiiError::UnexpectedContentType {
+            got: "text/html".to_string(),
+        };
+        assert_eq!(
+            error.to_string(),
+            "unexpected content type: expected JSON, got text/html"
+        );
+    }
+
+    #[test]
+    fn error_message_test() {
+        let body = r#"{"title":"Invalid filter","description":"match[type] is not supported","error_id":"e-1","http_status":"400"}"#;
+        let response = Response::new(400, "Bad Request", body).expect("Failed to build response");
+        let error = HttpError::from_response(response);
+        assert_eq!(error.status(), 400);
+        assert_eq!(error.body(), body);
+        let message = error.message().expect("No error message parsed");
+        assert_eq!(message.title, "Invalid filter");
+        assert_eq!(message.error_id.as_deref(), Some("e-1"));
+        assert_eq!(
+            TaxiiError::TaxiiBadRequest(error).to_string(),
+            "bad request: HTTP 400 Bad Request from https://example.com/: Invalid filter (match[type] is not supported)"
+        );
+        let response = Response::new(500, "Internal Server Error", "<html></html>")
+            .expect("Failed to build response");
+        assert!(HttpError::from_response(response).message().is_none());
+    }
 }
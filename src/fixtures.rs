@@ -0,0 +1,28 @@
+use crate::CCIndicator;
+
+/// The STIX pattern of mock indicators built without a pattern of their own.
+pub const PATTERN: &str = "[ipv4-addr:value = '198.51.100.1']";
+
+/// Builds the JSON of a mock STIX 2.1 indicator with the given id, `modified` timestamp, and
+/// pattern. Its name is its id and every other timestamp is `2024-01-01`.
+pub fn indicator_value(id: &str, modified: &str, pattern: &str) -> serde_json::Value {
+    serde_json::json!({
+        "created": "2024-01-01T00:00:00.000Z",
+        "description": "Mock indicator",
+        "id": id,
+        "modified": modified,
+        "name": id,
+        "pattern": pattern,
+        "pattern_type": "stix",
+        "pattern_version": "2.1",
+        "spec_version": "2.1",
+        "type": "indicator",
+        "valid_from": "2024-01-01T00:00:00Z",
+    })
+}
+
+/// Builds a mock indicator with the given id and pattern, as `indicator_value` describes.
+pub fn indicator(id: &str, pattern: &str) -> CCIndicator {
+    serde_json::from_value(indicator_value(id, "2024-01-01T00:00:00.000Z", pattern))
+        .expect("Failed to build indicator")
+}
@@ -1,7 +1,38 @@
+#[cfg(feature = "async")]
+mod asyncclient;
 mod cctaxiiclient;
+#[cfg(feature = "cef")]
+mod cef;
 mod error;
+#[cfg(test)]
+mod fixtures;
+mod metrics;
+#[cfg(any(test, feature = "mock"))]
+mod mock;
+mod pattern;
 mod taxiiclient;
+mod transport;
 
-pub use cctaxiiclient::{CCIndicator, CCTaxiiClient};
-pub use error::{Result, TaxiiError};
-pub use taxiiclient::{Collection, Collections, Discovery, Envelope, TaxiiClient};
+#[cfg(feature = "async")]
+pub use asyncclient::AsyncCCTaxiiClient;
+pub use cctaxiiclient::{
+    build_objects_url, filter_min_confidence, to_sighting_template, validate_bundle, validate_ids,
+    CCEnvelope, CCIndicator, CCTaxiiClient, CCTaxiiClientBuilder, MergeReport, PartialIndicators,
+    RetryPolicy, SelfTestReport, SelfTestStep, SourceInfo, VersionFilter,
+};
+#[cfg(feature = "chrono")]
+pub use cctaxiiclient::{filter_valid_from_between, taxii_timestamp};
+#[cfg(feature = "cef")]
+pub use cef::to_cef;
+pub use error::{HttpError, Result, TaxiiError, TaxiiErrorMessage};
+pub use metrics::Metrics;
+#[cfg(feature = "mock")]
+pub use mock::MockTaxiiClient;
+pub use pattern::{
+    extract_observables, parse_comparison, parse_pattern, partition_by_parseability, sigma_rules,
+    Comparison, Observable, PatternExpr,
+};
+pub use taxiiclient::{
+    compare_collections, ApiRoot, Collection, CollectionComparison, Collections, Discovery,
+    Envelope, Manifest, ManifestRecord, ServerOverview, Status, StatusDetails, TaxiiClient,
+};
@@ -0,0 +1,37 @@
+use crate::TaxiiError;
+
+/// A sink for client metrics, such as a bridge into a production metrics system.
+///
+/// Every callback has a no-op default, so implementors only override the events they record.
+/// The client calls the sink synchronously, so callbacks should be cheap.
+///
+/// # Examples
+///
+/// ```
+/// struct RequestCounter(AtomicU64);
+///
+/// impl Metrics for RequestCounter {
+///     fn on_request(&self, _method: &str, _url: &str) {
+///         self.0.fetch_add(1, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key")
+///     .with_metrics(RequestCounter(AtomicU64::new(0)));
+/// ```
+pub trait Metrics: Send + Sync {
+    /// Called before each request is sent, with its HTTP method and URL path.
+    fn on_request(&self, _method: &str, _url: &str) {}
+
+    /// Called when a request fails or its response cannot be parsed. Match on the error to
+    /// count errors by variant.
+    fn on_error(&self, _error: &TaxiiError) {}
+
+    /// Called with the number of response body bytes read.
+    fn on_bytes(&self, _bytes: u64) {}
+}
+
+/// The default metrics sink, which records nothing.
+pub struct NoMetrics;
+
+impl Metrics for NoMetrics {}
@@ -0,0 +1,198 @@
+use crate::{
+    taxiiclient::Discovery, transport::status_error, Result, TaxiiClient,
+    TaxiiError::JsonDeserializationError,
+};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use ureq::Response;
+
+/// A `TaxiiClient` serving canned responses, for testing code that uses a client without a
+/// network. Available with the `mock` feature.
+///
+/// Requests to URLs without a canned response, and calls to `get_discovery` or
+/// `get_collections` without canned data, fail with `TaxiiNotFound`, as a server would.
+/// Every request is recorded so tests can assert what was sent.
+///
+/// # Fields
+///
+/// - `discovery`: The discovery information returned by `get_discovery`, if set.
+/// - `collections`: The collection IDs returned by `get_collections`, keyed by API root.
+/// - `responses`: The status code and body returned for each request URL.
+/// - `private_root`: The private API root, if set.
+/// - `requests`: The method and URL of every request made, oldest first.
+///
+/// # Examples
+///
+/// ```
+/// let mock = MockTaxiiClient::new("user", "key")
+///     .with_collections("api", &["c1"])
+///     .with_response(
+///         "api/collections/c1/objects/",
+///         200,
+///         r#"{"objects":[{"id":"indicator--1"}]}"#,
+///     );
+/// let client: &dyn TaxiiClient = &mock;
+/// ```
+#[derive(Default)]
+pub struct MockTaxiiClient {
+    discovery: Option<Discovery>,
+    collections: HashMap<String, Vec<String>>,
+    responses: HashMap<String, (u16, String)>,
+    private_root: Option<String>,
+    requests: Mutex<Vec<(String, String)>>,
+}
+
+impl MockTaxiiClient {
+    /// Sets the discovery information returned by `get_discovery`.
+    #[must_use]
+    pub fn with_discovery(mut self, discovery: Discovery) -> Self {
+        self.discovery = Some(discovery);
+        self
+    }
+
+    /// Sets the collection IDs returned by `get_collections` for `root`. A `root` of `None`
+    /// is looked up as `api`.
+    #[must_use]
+    pub fn with_collections(mut self, root: &str, ids: &[&str]) -> Self {
+        self.collections.insert(
+            root.to_string(),
+            ids.iter().map(ToString::to_string).collect(),
+        );
+        self
+    }
+
+    /// Sets the status code and body returned for requests to `url`, such as the objects
+    /// endpoint of a collection. Query strings are part of the URL.
+    #[must_use]
+    pub fn with_response(mut self, url: &str, status: u16, body: &str) -> Self {
+        self.responses
+            .insert(url.to_string(), (status, body.to_string()));
+        self
+    }
+
+    /// Sets the API root returned by `private_root`.
+    #[must_use]
+    pub fn with_private_root(mut self, root: &str) -> Self {
+        self.private_root = Some(root.to_string());
+        self
+    }
+
+    /// Returns the method and URL of every request made so far, oldest first.
+    #[must_use]
+    pub fn requests(&self) -> Vec<(String, String)> {
+        self.requests
+            .lock()
+            .map(|requests| requests.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl TaxiiClient for MockTaxiiClient {
+    fn new(_username: &str, _api_key: &str) -> Self {
+        Self::default()
+    }
+
+    fn request_method(&self, method: &str, url: &str, _body: Option<&[u8]>) -> Result<Response> {
+        if let Ok(mut requests) = self.requests.lock() {
+            requests.push((method.to_string(), url.to_string()));
+        }
+        let (status, body) = self
+            .responses
+            .get(url)
+            .map_or((404, ""), |(status, body)| (*status, body.as_str()));
+        let response = Response::new(status, "", body)
+            .map_err(|e| Box::new(JsonDeserializationError(e.to_string())))?;
+        if (200..300).contains(&status) {
+            Ok(response)
+        } else {
+            Err(status_error(status, response))
+        }
+    }
+
+    fn get_discovery(&self) -> Result<Discovery> {
+        self.discovery.clone().map_or_else(
+            || {
+                let response = self.request("taxii2/")?;
+                response
+                    .into_json()
+                    .map_err(|e| Box::new(JsonDeserializationError(e.to_string())))
+            },
+            Ok,
+        )
+    }
+
+    fn get_collections(&self, root: Option<&str>) -> Result<Vec<String>> {
+        let root = root.unwrap_or("api");
+        self.collections.get(root).map_or_else(
+            || Err(status_error(404, not_found()?)),
+            |ids| Ok(ids.clone()),
+        )
+    }
+
+    fn private_root(&self) -> Option<&str> {
+        self.private_root.as_deref()
+    }
+}
+
+/// Builds an empty 404 response.
+fn not_found() -> Result<Response> {
+    Response::new(404, "Not Found", "")
+        .map_err(|e| Box::new(JsonDeserializationError(e.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts the objects in the default collection, as downstream code might.
+    fn count_default_objects(client: &dyn TaxiiClient, private: bool) -> Result<usize> {
+        let envelope = client.get_default_objects(private)?;
+        Ok(envelope.objects.len())
+    }
+
+    #[test]
+    fn mock_client_test() {
+        let mock = MockTaxiiClient::new("user", "key")
+            .with_discovery(Discovery {
+                api_roots: vec!["/feed/".to_string()],
+                contact: "contact@example.com".to_string(),
+                default: "/feed/".to_string(),
+                description: "Mock server".to_string(),
+                title: "Mock".to_string(),
+            })
+            .with_collections("feed", &["c2", "c1"])
+            .with_collections("acct", &["p1"])
+            .with_private_root("acct")
+            .with_response(
+                "feed/collections/c1/objects/",
+                200,
+                r#"{"more":false,"objects":[{"id":"indicator--1"},{"id":"indicator--2"}]}"#,
+            )
+            .with_response("acct/collections/p1/objects/", 401, "");
+        assert_eq!(count_default_objects(&mock, false).ok(), Some(2));
+        assert!(matches!(
+            count_default_objects(&mock, true).map_err(|e| *e),
+            Err(crate::TaxiiError::TaxiiAuthorizationError(_))
+        ));
+        assert_eq!(
+            mock.requests(),
+            [
+                (
+                    "GET".to_string(),
+                    "feed/collections/c1/objects/".to_string()
+                ),
+                (
+                    "GET".to_string(),
+                    "acct/collections/p1/objects/".to_string()
+                ),
+            ]
+        );
+
+        let empty = MockTaxiiClient::new("user", "key");
+        assert!(matches!(
+            empty.get_discovery().map_err(|e| *e),
+            Err(crate::TaxiiError::TaxiiNotFound(_))
+        ));
+        assert!(empty.get_collections(None).is_err());
+    }
+}
@@ -0,0 +1,528 @@
+use crate::CCIndicator;
+
+/// A single STIX pattern comparison, such as `[ipv4-addr:value = '198.51.100.1']`.
+///
+/// # Fields
+///
+/// - `object_path`: The object path being compared (e.g., `ipv4-addr:value`).
+/// - `operator`: The comparison operator (e.g., `=`, `!=`, `LIKE`, `MATCHES`).
+/// - `negated`: Whether the operator was preceded by `NOT`.
+/// - `value`: The constant compared against, with string quoting and escapes removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comparison {
+    pub object_path: String,
+    pub operator: String,
+    pub negated: bool,
+    pub value: String,
+}
+
+impl Comparison {
+    /// Returns the STIX Cyber-observable Object type of the object path (e.g., `ipv4-addr`).
+    #[must_use]
+    pub fn object_type(&self) -> &str {
+        self.object_path
+            .split_once(':')
+            .map_or(self.object_path.as_str(), |(object_type, _)| object_type)
+    }
+}
+
+/// A STIX pattern parsed into a tree of comparisons and the operators combining them.
+///
+/// `And` and `Or` are used both for comparisons combined inside one observation
+/// (`[a:x = '1' AND a:y = '2']`) and for observations combined with each other
+/// (`[a:x = '1'] AND [b:y = '2']`). Operators of the same kind nest to the left.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PatternExpr {
+    /// A single comparison.
+    Comparison(Comparison),
+    /// Both sides must match.
+    And(Box<Self>, Box<Self>),
+    /// Either side must match.
+    Or(Box<Self>, Box<Self>),
+    /// The left side must match before the right side.
+    FollowedBy(Box<Self>, Box<Self>),
+}
+
+/// Parses a STIX pattern combining comparisons with `AND`, `OR`, and `FOLLOWEDBY`.
+///
+/// Observations and comparisons may be grouped with parentheses. Following the STIX
+/// precedence rules, `AND` binds tighter than `OR`, which binds tighter than `FOLLOWEDBY`.
+/// Patterns using qualifiers or comparing against sets, and malformed patterns, return `None`.
+///
+/// # Examples
+///
+/// ```
+/// let expr = parse_pattern("[ipv4-addr:value = '198.51.100.1'] OR [domain-name:value = 'example.com']");
+/// assert!(matches!(expr, Some(PatternExpr::Or(_, _))));
+/// ```
+#[must_use]
+pub fn parse_pattern(pattern: &str) -> Option<PatternExpr> {
+    if let Some(comparison) = parse_comparison(pattern) {
+        return Some(PatternExpr::Comparison(comparison));
+    }
+    let mut parser = Parser::new(pattern);
+    let expr = parser.followed_by()?;
+    parser.skip_whitespace();
+    parser.at_end().then_some(expr)
+}
+
+/// Parses a STIX pattern made of exactly one bracketed comparison.
+///
+/// Patterns combining several comparisons or observations, using qualifiers, or comparing
+/// against sets are not single comparisons and return `None`, as do malformed patterns.
+///
+/// # Examples
+///
+/// ```
+/// let comparison = parse_comparison("[ipv4-addr:value = '198.51.100.1']");
+/// assert_eq!(comparison.map(|c| c.value), Some("198.51.100.1".to_string()));
+/// ```
+#[must_use]
+pub fn parse_comparison(pattern: &str) -> Option<Comparison> {
+    let mut parser = Parser::new(pattern);
+    parser.expect('[')?;
+    let comparison = parser.comparison()?;
+    parser.expect(']')?;
+    parser.skip_whitespace();
+    parser.at_end().then_some(comparison)
+}
+
+/// Splits indicators into those whose STIX pattern can be parsed and those that cannot.
+///
+/// An indicator is parseable when its `pattern_type` is `stix` and its pattern is a single
+/// comparison understood by [`parse_comparison`]. This lets callers feed the first group to a
+/// matcher while logging the second.
+///
+/// # Returns
+///
+/// Returns `(parseable, unparseable)`, each keeping the original order.
+#[must_use]
+pub fn partition_by_parseability(
+    indicators: Vec<CCIndicator>,
+) -> (Vec<CCIndicator>, Vec<CCIndicator>) {
+    indicators.into_iter().partition(|indicator| {
+        indicator.pattern_type == "stix" && parse_comparison(&indicator.pattern).is_some()
+    })
+}
+
+/// An observable extracted from the STIX pattern of an indicator.
+///
+/// # Fields
+///
+/// - `value`: The observed value (e.g., `198.51.100.1`).
+/// - `kind`: The object path the value was compared against (e.g., `ipv4-addr:value` or
+///   `file:hashes.'SHA-256'`).
+/// - `labels`: The STIX `labels` of the indicator, such as `malicious-activity`, for
+///   tagging the observable downstream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observable {
+    pub value: String,
+    pub kind: String,
+    pub labels: Vec<String>,
+}
+
+/// Extracts the observables of indicators whose `pattern_type` is `stix`.
+///
+/// Every equality comparison in a pattern yields one observable, so a pattern combining
+/// several comparisons yields several. Negated and non-equality comparisons, and patterns
+/// that cannot be parsed with [`parse_pattern`], are skipped. Each observable carries the
+/// `labels` of its indicator.
+///
+/// # Examples
+///
+/// ```
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+/// let indicators = agent.get_cc_indicators(None, None, false, None, &None, true)?;
+/// for observable in extract_observables(&indicators) {
+///     blocklist.add(&observable.kind, &observable.value, &observable.labels);
+/// }
+/// ```
+#[must_use]
+pub fn extract_observables(indicators: &[CCIndicator]) -> Vec<Observable> {
+    indicators.iter().flat_map(indicator_observables).collect()
+}
+
+/// Extracts the observables of a single indicator, as `extract_observables` does.
+#[allow(clippy::redundant_pub_crate)]
+#[must_use]
+pub(crate) fn indicator_observables(indicator: &CCIndicator) -> Vec<Observable> {
+    let mut comparisons = Vec::new();
+    if indicator.pattern_type == "stix" {
+        if let Some(expr) = parse_pattern(&indicator.pattern) {
+            collect_comparisons(expr, &mut comparisons);
+        }
+    }
+    comparisons
+        .into_iter()
+        .filter(|comparison| comparison.operator == "=" && !comparison.negated)
+        .map(|comparison| Observable {
+            value: comparison.value,
+            kind: comparison.object_path,
+            labels: indicator.labels.clone(),
+        })
+        .collect()
+}
+
+/// Appends the comparisons of `expr` to `comparisons`, left to right.
+fn collect_comparisons(expr: PatternExpr, comparisons: &mut Vec<Comparison>) {
+    match expr {
+        PatternExpr::Comparison(comparison) => comparisons.push(comparison),
+        PatternExpr::And(left, right)
+        | PatternExpr::Or(left, right)
+        | PatternExpr::FollowedBy(left, right) => {
+            collect_comparisons(*left, comparisons);
+            collect_comparisons(*right, comparisons);
+        }
+    }
+}
+
+/// Returns the raw Sigma rule bodies of the indicators whose `pattern_type` is `sigma`.
+///
+/// Indicators of any other pattern type are skipped.
+///
+/// # Examples
+///
+/// ```
+/// let agent = CCTaxiiClient::new("my_username", "my_api_key");
+/// let indicators = agent.get_cc_indicators(None, None, false, None, &None, true)?;
+/// for rule in sigma_rules(&indicators) {
+///     // Load the rule into the Sigma backend
+/// }
+/// ```
+#[must_use]
+pub fn sigma_rules(indicators: &[CCIndicator]) -> Vec<String> {
+    indicators
+        .iter()
+        .filter(|indicator| indicator.pattern_type == "sigma")
+        .map(|indicator| indicator.pattern.clone())
+        .collect()
+}
+
+/// A cursor over the characters of a STIX pattern.
+struct Parser<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Parser<'a> {
+    const fn new(pattern: &'a str) -> Self {
+        Self { rest: pattern }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.rest = self.rest.trim_start();
+    }
+
+    const fn at_end(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest.chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.rest = &self.rest[c.len_utf8()..];
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Option<()> {
+        self.skip_whitespace();
+        (self.bump()? == expected).then_some(())
+    }
+
+    /// Consumes `keyword` if it is the next word, ignoring case.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.skip_whitespace();
+        let Some(candidate) = self.rest.get(..keyword.len()) else {
+            return false;
+        };
+        let boundary = self.rest[keyword.len()..]
+            .chars()
+            .next()
+            .map_or(true, |c| !c.is_ascii_alphanumeric() && c != '_');
+        if candidate.eq_ignore_ascii_case(keyword) && boundary {
+            self.rest = &self.rest[keyword.len()..];
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses operands separated by `keyword`, combining them to the left with `combine`.
+    fn binary(
+        &mut self,
+        keyword: &str,
+        operand: fn(&mut Self) -> Option<PatternExpr>,
+        combine: fn(Box<PatternExpr>, Box<PatternExpr>) -> PatternExpr,
+    ) -> Option<PatternExpr> {
+        let mut expr = operand(self)?;
+        while self.keyword(keyword) {
+            expr = combine(Box::new(expr), Box::new(operand(self)?));
+        }
+        Some(expr)
+    }
+
+    /// Consumes `c` if it is the next non-whitespace character.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn followed_by(&mut self) -> Option<PatternExpr> {
+        self.binary("FOLLOWEDBY", Self::observation_or, PatternExpr::FollowedBy)
+    }
+
+    fn observation_or(&mut self) -> Option<PatternExpr> {
+        self.binary("OR", Self::observation_and, PatternExpr::Or)
+    }
+
+    fn observation_and(&mut self) -> Option<PatternExpr> {
+        self.binary("AND", Self::observation, PatternExpr::And)
+    }
+
+    fn observation(&mut self) -> Option<PatternExpr> {
+        if self.eat('(') {
+            let expr = self.followed_by()?;
+            return self.eat(')').then_some(expr);
+        }
+        self.expect('[')?;
+        let expr = self.comparison_or()?;
+        self.expect(']')?;
+        Some(expr)
+    }
+
+    fn comparison_or(&mut self) -> Option<PatternExpr> {
+        self.binary("OR", Self::comparison_and, PatternExpr::Or)
+    }
+
+    fn comparison_and(&mut self) -> Option<PatternExpr> {
+        self.binary("AND", Self::comparison_group, PatternExpr::And)
+    }
+
+    fn comparison_group(&mut self) -> Option<PatternExpr> {
+        if self.eat('(') {
+            let expr = self.comparison_or()?;
+            return self.eat(')').then_some(expr);
+        }
+        self.comparison().map(PatternExpr::Comparison)
+    }
+
+    fn comparison(&mut self) -> Option<Comparison> {
+        let object_path = self.object_path()?;
+        let negated = self.keyword("NOT");
+        let operator = self.operator()?;
+        let value = self.value()?;
+        Some(Comparison {
+            object_path,
+            operator,
+            negated,
+            value,
+        })
+    }
+
+    fn object_path(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let mut path = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '\'' => {
+                    path.push(c);
+                    self.bump();
+                    while let Some(quoted) = self.bump() {
+                        path.push(quoted);
+                        if quoted == '\'' {
+                            break;
+                        }
+                    }
+                }
+                c if c.is_whitespace() || "=!<>]()".contains(c) => break,
+                _ => {
+                    path.push(c);
+                    self.bump();
+                }
+            }
+        }
+        let (object_type, property) = path.split_once(':')?;
+        let valid_type = !object_type.is_empty()
+            && object_type
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-');
+        (valid_type && !property.is_empty()).then_some(path)
+    }
+
+    fn operator(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        for symbol in ["!=", ">=", "<=", "=", ">", "<"] {
+            if let Some(rest) = self.rest.strip_prefix(symbol) {
+                self.rest = rest;
+                return Some(symbol.to_string());
+            }
+        }
+        ["LIKE", "MATCHES", "ISSUBSET", "ISSUPERSET"]
+            .into_iter()
+            .find(|keyword| self.keyword(keyword))
+            .map(str::to_string)
+    }
+
+    fn value(&mut self) -> Option<String> {
+        self.skip_whitespace();
+        let prefixed = self
+            .rest
+            .strip_prefix(['t', 'h', 'b'])
+            .filter(|rest| rest.starts_with('\''));
+        if let Some(rest) = prefixed {
+            self.rest = rest;
+        }
+        if self.peek()? == '\'' {
+            self.bump();
+            let mut value = String::new();
+            loop {
+                match self.bump()? {
+                    '\\' => value.push(self.bump()?),
+                    '\'' => return Some(value),
+                    c => value.push(c),
+                }
+            }
+        }
+        let end = self
+            .rest
+            .find(|c: char| c.is_whitespace() || c == ']' || c == ')')
+            .unwrap_or(self.rest.len());
+        let (literal, rest) = self.rest.split_at(end);
+        let valid = literal == "true" || literal == "false" || literal.parse::<f64>().is_ok();
+        self.rest = rest;
+        valid.then(|| literal.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::indicator;
+
+    #[test]
+    fn parse_comparison_test() {
+        let comparison =
+            parse_comparison("[file:hashes.'SHA-256' = 'ab\\'cd']").expect("Failed to parse");
+        assert_eq!(comparison.object_path, "file:hashes.'SHA-256'");
+        assert_eq!(comparison.object_type(), "file");
+        assert_eq!(comparison.operator, "=");
+        assert!(!comparison.negated);
+        assert_eq!(comparison.value, "ab'cd");
+
+        let comparison =
+            parse_comparison("[domain-name:value NOT LIKE '%.example.com']").expect("Failed");
+        assert_eq!(comparison.operator, "LIKE");
+        assert!(comparison.negated);
+
+        assert!(parse_comparison("[ipv4-addr:value = '1.2.3.4'] WITHIN 5 SECONDS").is_none());
+        assert!(parse_comparison("ipv4-addr:value = '1.2.3.4'").is_none());
+    }
+
+    #[test]
+    fn partition_by_parseability_test() {
+        let indicators = vec![
+            indicator("simple", "[ipv4-addr:value = '198.51.100.1']"),
+            indicator(
+                "complex",
+                "[ipv4-addr:value = '198.51.100.1'] AND [domain-name:value = 'example.com']",
+            ),
+        ];
+        let (parseable, unparseable) = partition_by_parseability(indicators);
+        assert_eq!(parseable.len(), 1);
+        assert_eq!(parseable[0].id, "simple");
+        assert_eq!(unparseable.len(), 1);
+        assert_eq!(unparseable[0].id, "complex");
+    }
+
+    fn comparison_expr(object_path: &str, value: &str) -> PatternExpr {
+        PatternExpr::Comparison(Comparison {
+            object_path: object_path.to_string(),
+            operator: "=".to_string(),
+            negated: false,
+            value: value.to_string(),
+        })
+    }
+
+    #[test]
+    fn parse_pattern_and_test() {
+        assert_eq!(
+            parse_pattern("[a:x = '1'] AND [b:y = '2']"),
+            Some(PatternExpr::And(
+                Box::new(comparison_expr("a:x", "1")),
+                Box::new(comparison_expr("b:y", "2"))
+            ))
+        );
+        assert_eq!(
+            parse_pattern("[a:x = '1']"),
+            Some(PatternExpr::Comparison(
+                parse_comparison("[a:x = '1']").expect("Failed to parse")
+            ))
+        );
+    }
+
+    #[test]
+    fn parse_pattern_or_test() {
+        assert_eq!(
+            parse_pattern("[a:x = '1' OR a:x = '2' AND b:y = 3] FOLLOWEDBY ([c:z = '4'])"),
+            Some(PatternExpr::FollowedBy(
+                Box::new(PatternExpr::Or(
+                    Box::new(comparison_expr("a:x", "1")),
+                    Box::new(PatternExpr::And(
+                        Box::new(comparison_expr("a:x", "2")),
+                        Box::new(comparison_expr("b:y", "3"))
+                    ))
+                )),
+                Box::new(comparison_expr("c:z", "4"))
+            ))
+        );
+        assert!(parse_pattern("[a:x = '1'] OR").is_none());
+        assert!(parse_pattern("[a:x = '1'] WITHIN 5 SECONDS").is_none());
+    }
+
+    #[test]
+    fn sigma_rules_test() {
+        let rule = "title: Suspicious Process\ndetection:\n  condition: selection";
+        let mut sigma = indicator("sigma", rule);
+        sigma.pattern_type = "sigma".to_string();
+        let indicators = [
+            indicator("stix", "[ipv4-addr:value = '198.51.100.1']"),
+            sigma,
+        ];
+        assert_eq!(sigma_rules(&indicators), [rule]);
+    }
+
+    #[test]
+    fn extract_observables_test() {
+        let mut labelled = indicator(
+            "labelled",
+            "[ipv4-addr:value = '198.51.100.1' OR domain-name:value != 'example.com']",
+        );
+        labelled.labels = vec!["malicious-activity".to_string()];
+        let indicators = [
+            labelled,
+            indicator("bare", "[url:value = 'https://example.com/']"),
+        ];
+        assert_eq!(
+            extract_observables(&indicators),
+            [
+                Observable {
+                    value: "198.51.100.1".to_string(),
+                    kind: "ipv4-addr:value".to_string(),
+                    labels: vec!["malicious-activity".to_string()],
+                },
+                Observable {
+                    value: "https://example.com/".to_string(),
+                    kind: "url:value".to_string(),
+                    labels: Vec::new(),
+                },
+            ]
+        );
+    }
+}
@@ -1,5 +1,9 @@
-use crate::Result;
-use serde::Deserialize;
+use crate::{
+    Result,
+    TaxiiError::{JsonDeserializationError, TaxiiCollectionError},
+};
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::collections::HashMap;
 use ureq::Response;
 
@@ -49,11 +53,45 @@ pub trait TaxiiClient {
     where
         Self: Sized;
 
+    /// Sends a request with the given HTTP method and optional body to the specified URL.
+    ///
+    /// This method constructs and sends an HTTP request to the given URL. It includes common
+    /// headers set during the construction of the `TaxiiClient` instance, so every verb shares
+    /// the same authentication and error handling. The method handles HTTP errors and returns
+    /// the `Response`.
+    ///
+    /// # Parameters
+    ///
+    /// - `method`: The HTTP method, such as `GET` or `POST`.
+    /// - `url`: The URL path to append to the base URL of the TAXII server.
+    /// - `body`: The request body, or `None` to send no body.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Response)` if the request is successful.
+    /// Returns `Err(TaxiiError)` if the request fails or the server responds with an error.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `TaxiiBadRequest` if the response status code is 400 (Bad Request).
+    /// - Returns `TaxiiAuthorizationError` if the response status code is 401 (Unauthorized).
+    /// - Returns `TaxiiNotFound` if the response status code is 404 (Not Found).
+    /// - Returns `TaxiiRateLimited` if the response status code is 429 (Too Many Requests).
+    /// - Returns `TaxiiServerError` for 5xx status codes.
+    /// - Returns `TaxiiGenericError` for other non-successful status codes.
+    /// - Returns `TaxiiConnectionError` if the request fails to execute.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = TaxiiClient::new("my_username", "my_api_key");
+    /// let response = agent.request_method("POST", "api/collections/id/objects/", Some(b"{}"));
+    /// ```
+    fn request_method(&self, method: &str, url: &str, body: Option<&[u8]>) -> Result<Response>;
+
     /// Sends a GET request to the specified URL.
     ///
-    /// This method constructs and sends an HTTP GET request to the given URL. It includes
-    /// common headers set during the construction of the `TaxiiClient` instance. The method
-    /// handles HTTP errors and deserializes the response into a `Response`.
+    /// This is a thin wrapper around `request_method` that sends a GET request without a body.
     ///
     /// # Parameters
     ///
@@ -66,8 +104,11 @@ pub trait TaxiiClient {
     ///
     /// # Errors
     ///
+    /// - Returns `TaxiiBadRequest` if the response status code is 400 (Bad Request).
     /// - Returns `TaxiiAuthorizationError` if the response status code is 401 (Unauthorized).
-    /// - Returns `TaxiiNotFoundError` if the response status code is 404 (Not Found).
+    /// - Returns `TaxiiNotFound` if the response status code is 404 (Not Found).
+    /// - Returns `TaxiiRateLimited` if the response status code is 429 (Too Many Requests).
+    /// - Returns `TaxiiServerError` for 5xx status codes.
     /// - Returns `TaxiiGenericError` for other non-successful status codes.
     /// - Returns `TaxiiConnectionError` if the request fails to execute.
     ///
@@ -77,7 +118,9 @@ pub trait TaxiiClient {
     /// let agent = TaxiiClient::new("my_username", "my_api_key");
     /// let response = agent.request("taxii2/");
     /// ```
-    fn request(&self, url: &str) -> Result<Response>;
+    fn request(&self, url: &str) -> Result<Response> {
+        self.request_method("GET", url, None)
+    }
 
     /// Retrieves discovery information from the TAXII server.
     ///
@@ -130,23 +173,376 @@ pub trait TaxiiClient {
     /// let collections = agent.get_collections(Some("api"));
     /// ```
     fn get_collections(&self, root: Option<&str>) -> Result<Vec<String>>;
+
+    /// Returns the API root holding the client's private collections, if it has one.
+    ///
+    /// The default implementation returns `None`, meaning the client has no private root.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = TaxiiClient::new("my_username", "my_api_key");
+    /// let root = agent.private_root();
+    /// ```
+    fn private_root(&self) -> Option<&str> {
+        None
+    }
+
+    /// Retrieves the objects of the default collection of the default or private API root.
+    ///
+    /// This method is composed entirely of other trait methods, so every implementor gets it
+    /// for free. The public root is taken from the last path segment of the discovery `default`
+    /// entry, and the private root from `private_root`. The first page of objects of the
+    /// root's collection with the lexicographically smallest ID is returned, so the choice
+    /// does not depend on the order the server lists collections in.
+    ///
+    /// # Parameters
+    ///
+    /// - `private`: Whether to use the private API root (`true`) or the server's default API
+    ///   root (`false`).
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Envelope<serde_json::Value>)` with the first page of objects if the requests
+    /// are successful, keeping every object as raw JSON whatever the types of its properties.
+    /// Returns `Err(TaxiiError)` if any request fails or a response cannot be deserialized.
+    ///
+    /// # Errors
+    ///
+    /// - Returns `TaxiiCollectionError` if there is no private root or the root has no collections.
+    /// - Returns a deserialization error if the objects response is not an envelope.
+    /// - Returns any error from `get_discovery`, `get_collections`, or `request`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = TaxiiClient::new("my_username", "my_api_key");
+    /// let envelope = agent.get_default_objects(false);
+    /// ```
+    fn get_default_objects(&self, private: bool) -> Result<Envelope<serde_json::Value>> {
+        let root = if private {
+            self.private_root()
+                .ok_or_else(|| TaxiiCollectionError("No private root available".to_string()))?
+                .to_string()
+        } else {
+            let discovery = self.get_discovery()?;
+            discovery
+                .default
+                .trim_end_matches('/')
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        };
+        let collection = self
+            .get_collections(Some(&root))?
+            .into_iter()
+            .min()
+            .ok_or_else(|| TaxiiCollectionError("No collections available".to_string()))?;
+        let response = self.request(&self.objects_path(&root, &collection))?;
+        response
+            .into_json()
+            .map_err(|e| Box::new(JsonDeserializationError(e.to_string())))
+    }
+
+    /// Returns the path of the objects endpoint of a collection, relative to the server.
+    ///
+    /// The default implementation returns the standard TAXII 2.1 path
+    /// `{root}/collections/{collection_id}/objects/`. Implementors serving objects elsewhere
+    /// override it, and `get_default_objects`, `get_objects`, and `get_objects_json` follow.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The API root holding the collection.
+    /// - `collection_id`: The ID of the collection.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = TaxiiClient::new("my_username", "my_api_key");
+    /// assert_eq!(agent.objects_path("api", "c1"), "api/collections/c1/objects/");
+    /// ```
+    fn objects_path(&self, root: &str, collection_id: &str) -> String {
+        format!("{root}/collections/{collection_id}/objects/")
+    }
+
+    /// Retrieves every object of a collection, following pagination.
+    ///
+    /// Like `get_default_objects`, this method only relies on `request`, so any implementor
+    /// gets object fetching for a standard TAXII 2.1 server for free. Pages are requested from
+    /// `objects_path`, passing each page's `next` cursor to the following request, until the
+    /// server reports no more pages or repeats a cursor. The objects of all pages are
+    /// returned in one envelope. Each object is deserialized into `T`, such as
+    /// `serde_json::Value` for arbitrary STIX objects. Being generic, this method cannot be
+    /// called through `&dyn TaxiiClient`; use `get_objects_json` there.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The API root holding the collection.
+    /// - `collection_id`: The ID of the collection.
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(Envelope<T>)` with the objects of all pages if the requests are successful.
+    /// Returns `Err(TaxiiError)` if any request fails or a page cannot be deserialized.
+    ///
+    /// # Errors
+    ///
+    /// - Returns a deserialization error if a page cannot be parsed into an `Envelope<T>`.
+    /// - Returns any error from `request`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent = TaxiiClient::new("my_username", "my_api_key");
+    /// let envelope: Envelope<serde_json::Value> = agent.get_objects("api", "collection_id")?;
+    /// ```
+    fn get_objects<T: DeserializeOwned>(
+        &self,
+        root: &str,
+        collection_id: &str,
+    ) -> Result<Envelope<T>>
+    where
+        Self: Sized,
+    {
+        fetch_all_objects(self, root, collection_id)
+    }
+
+    /// Retrieves every object of a collection as raw JSON, following pagination.
+    ///
+    /// This is `get_objects` with the objects kept as `serde_json::Value`, so it can be called
+    /// through `&dyn TaxiiClient`.
+    ///
+    /// # Parameters
+    ///
+    /// - `root`: The API root holding the collection.
+    /// - `collection_id`: The ID of the collection.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as `get_objects`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let agent: &dyn TaxiiClient = &client;
+    /// let envelope = agent.get_objects_json("api", "collection_id")?;
+    /// ```
+    fn get_objects_json(
+        &self,
+        root: &str,
+        collection_id: &str,
+    ) -> Result<Envelope<serde_json::Value>> {
+        fetch_all_objects(self, root, collection_id)
+    }
+}
+
+/// Reads every page of the objects endpoint of a collection into one envelope, stopping when
+/// the server reports no more pages or repeats a cursor.
+fn fetch_all_objects<C, T>(client: &C, root: &str, collection_id: &str) -> Result<Envelope<T>>
+where
+    C: TaxiiClient + ?Sized,
+    T: DeserializeOwned,
+{
+    let url = client.objects_path(root, collection_id);
+    let mut objects = Vec::new();
+    let mut cursors: Vec<String> = Vec::new();
+    loop {
+        let page_url = cursors.last().map_or_else(
+            || url.clone(),
+            |next| format!("{url}?next={}", utf8_percent_encode(next, NON_ALPHANUMERIC)),
+        );
+        let page: Envelope<T> = client
+            .request(&page_url)?
+            .into_json()
+            .map_err(|e| Box::new(JsonDeserializationError(e.to_string())))?;
+        objects.extend(page.objects);
+        match page.next {
+            Some(next) if page.more.unwrap_or(false) && !cursors.contains(&next) => {
+                cursors.push(next);
+            }
+            _ => break,
+        }
+    }
+    Ok(Envelope {
+        more: Some(false),
+        next: None,
+        objects,
+    })
 }
 
 /// Represents a TAXII Envelope, used for wrapping TAXII objects.
 ///
 /// The Envelope is a container for objects in TAXII, potentially including additional
-/// pagination information.
+/// pagination information. It is generic over the type of its objects: the default
+/// `Envelope` holds each object as a `HashMap<String, String>`, `CCEnvelope` is an
+/// `Envelope<CCIndicator>`, and any other `Deserialize` type can be plugged in.
+///
+/// # Fields
+///
+/// - `more`: Indicates if more data is available (pagination).
+/// - `next`: The pagination cursor for the next page, if `more` is `true`.
+/// - `objects`: The objects of the page, empty if the server sent none. A lone object sent
+///   in place of an array is accepted as a single-element collection.
+///
+/// Absent optional fields, and an empty `objects`, are omitted when serializing.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Envelope<T = HashMap<String, String>> {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub more: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(
+        default = "Vec::new",
+        deserialize_with = "deserialize_one_or_many",
+        bound(deserialize = "T: Deserialize<'de>"),
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub objects: Vec<T>,
+}
+
+impl<T> Envelope<T> {
+    /// Returns whether the server indicated that more data is available.
+    #[must_use]
+    pub fn more(&self) -> bool {
+        self.more.unwrap_or(false)
+    }
+
+    /// Returns the pagination cursor for the next page, if the server sent one.
+    #[must_use]
+    pub fn next(&self) -> Option<&str> {
+        self.next.as_deref()
+    }
+
+    /// Returns the objects of the page.
+    #[must_use]
+    pub fn objects(&self) -> &[T] {
+        &self.objects
+    }
+}
+
+impl<T> IntoIterator for Envelope<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    /// Iterates over the objects of the envelope.
+    fn into_iter(self) -> Self::IntoIter {
+        self.objects.into_iter()
+    }
+}
+
+/// Deserializes either an array or a single value into a `Vec`.
+///
+/// Some nonconformant servers send a lone object instead of a one-element array.
+fn deserialize_one_or_many<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        Many(Vec<T>),
+        One(T),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::Many(values) => values,
+        OneOrMany::One(value) => vec![value],
+    })
+}
+
+/// A page of a collection's manifest, listing metadata about the objects it holds.
 ///
 /// # Fields
 ///
 /// - `more`: Indicates if more data is available (pagination).
 /// - `next`: The URL for the next set of data, if `more` is `true`.
-/// - `objects`: A collection of TAXII objects, each represented as a `HashMap<String, String>`.
+/// - `objects`: The manifest records of this page, absent if there are none.
 #[derive(Deserialize, Debug)]
-pub struct Envelope {
+pub struct Manifest {
     pub more: Option<bool>,
     pub next: Option<String>,
-    pub objects: Option<Vec<HashMap<String, String>>>,
+    pub objects: Option<Vec<ManifestRecord>>,
+}
+
+/// Metadata about a single object in a collection, as listed in its manifest.
+///
+/// # Fields
+///
+/// - `id`: The STIX id of the object.
+/// - `date_added`: The date and time the object was added to the collection.
+/// - `version`: The version of the object, which is its `modified` timestamp.
+/// - `media_type`: The media type of this version of the object, if provided.
+#[derive(Deserialize, Debug)]
+pub struct ManifestRecord {
+    pub id: String,
+    pub date_added: String,
+    pub version: String,
+    pub media_type: Option<String>,
+}
+
+/// The status of a request to add objects to a collection.
+///
+/// A submitted batch may partially succeed, so the objects accepted, rejected, and still being
+/// processed are each listed.
+///
+/// # Fields
+///
+/// - `id`: The identifier of this status resource.
+/// - `status`: The overall status, either `complete` or `pending`.
+/// - `request_timestamp`: The date and time the request was received, if provided.
+/// - `total_count`: The total number of objects in the request.
+/// - `success_count`: The number of objects that were added.
+/// - `successes`: Details of the objects that were added, if provided.
+/// - `failure_count`: The number of objects that were not added.
+/// - `failures`: Details of the objects that were not added, if provided.
+/// - `pending_count`: The number of objects still being processed.
+/// - `pendings`: Details of the objects still being processed, if provided.
+#[derive(Deserialize, Debug)]
+pub struct Status {
+    pub id: String,
+    pub status: String,
+    pub request_timestamp: Option<String>,
+    pub total_count: u64,
+    pub success_count: u64,
+    pub successes: Option<Vec<StatusDetails>>,
+    pub failure_count: u64,
+    pub failures: Option<Vec<StatusDetails>>,
+    pub pending_count: u64,
+    pub pendings: Option<Vec<StatusDetails>>,
+}
+
+impl Status {
+    /// Returns the ids of the objects that were not added, so only those can be retried.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let retry: Vec<&str> = status.failed_ids();
+    /// ```
+    #[must_use]
+    pub fn failed_ids(&self) -> Vec<&str> {
+        self.failures
+            .iter()
+            .flatten()
+            .map(|failure| failure.id.as_str())
+            .collect()
+    }
+}
+
+/// The outcome for a single object listed in a `Status`.
+///
+/// # Fields
+///
+/// - `id`: The STIX id of the object.
+/// - `version`: The version of the object, which is its `modified` timestamp.
+/// - `message`: A message about the outcome, such as the reason the object was rejected.
+#[derive(Deserialize, Debug)]
+pub struct StatusDetails {
+    pub id: String,
+    pub version: String,
+    pub message: Option<String>,
 }
 
 /// Contains discovery information for a TAXII server.
@@ -161,7 +557,7 @@ pub struct Envelope {
 /// - `default`: The default API root for this server.
 /// - `description`: A human-readable description of this server.
 /// - `title`: A human-readable title for this server.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Discovery {
     pub api_roots: Vec<String>,
     pub contact: String,
@@ -170,6 +566,22 @@ pub struct Discovery {
     pub title: String,
 }
 
+/// Information about a single API root of a TAXII server.
+///
+/// # Fields
+///
+/// - `title`: A human-readable title for this API root.
+/// - `description`: A human-readable description of this API root, if provided.
+/// - `versions`: The TAXII versions supported by this API root.
+/// - `max_content_length`: The largest request body, in bytes, this API root accepts.
+#[derive(Deserialize, Debug, Clone)]
+pub struct ApiRoot {
+    pub title: String,
+    pub description: Option<String>,
+    pub versions: Vec<String>,
+    pub max_content_length: u64,
+}
+
 /// Represents a single collection within a TAXII server.
 ///
 /// A collection is a set of cyber threat intelligence expressed in STIX 2.0.
@@ -182,10 +594,15 @@ pub struct Discovery {
 /// - `media_types`: The media types supported by the collection.
 /// - `name`: The name of the collection.
 /// - `title`: A human-readable title for the collection.
+///
+/// `can_read` and `can_write` are also accepted as the strings `"true"` and `"false"`, which
+/// some servers send instead of JSON booleans.
 #[allow(dead_code)]
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Collection {
+    #[serde(deserialize_with = "deserialize_lenient_bool")]
     pub can_read: bool,
+    #[serde(deserialize_with = "deserialize_lenient_bool")]
     pub can_write: bool,
     pub id: String,
     pub media_types: [String; 1],
@@ -193,6 +610,156 @@ pub struct Collection {
     pub title: String,
 }
 
+impl Collection {
+    /// Normalizes a media type for comparison.
+    ///
+    /// The type and parameter names are lowercased, whitespace around them is removed, the
+    /// `charset` parameter is dropped, and the remaining parameters are sorted, so
+    /// `Application/STIX+JSON; charset=utf-8; version=2.1` becomes
+    /// `application/stix+json;version=2.1`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let normalized = Collection::normalize_media_type("application/stix+json; version=2.1");
+    /// assert_eq!(normalized, "application/stix+json;version=2.1");
+    /// ```
+    #[must_use]
+    pub fn normalize_media_type(media_type: &str) -> String {
+        let mut parts = media_type.split(';');
+        let essence = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let mut parameters: Vec<String> = parts
+            .filter_map(|parameter| {
+                let (name, value) = parameter.split_once('=')?;
+                let name = name.trim().to_ascii_lowercase();
+                (name != "charset").then(|| format!("{name}={}", value.trim()))
+            })
+            .collect();
+        parameters.sort_unstable();
+        std::iter::once(essence)
+            .chain(parameters)
+            .collect::<Vec<_>>()
+            .join(";")
+    }
+
+    /// Returns `true` if the collection supports `media_type`.
+    ///
+    /// Media types are compared after [`normalize_media_type`](Self::normalize_media_type).
+    /// A bare type such as `application/stix+json` matches any version the collection
+    /// advertises, while a type with parameters must match them exactly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// if collection.supports_media_type("application/stix+json") {
+    ///     // Request STIX objects from the collection
+    /// }
+    /// ```
+    #[must_use]
+    pub fn supports_media_type(&self, media_type: &str) -> bool {
+        let wanted = Self::normalize_media_type(media_type);
+        self.media_types.iter().any(|supported| {
+            let supported = Self::normalize_media_type(supported);
+            supported == wanted
+                || (!wanted.contains(';') && supported.split(';').next() == Some(wanted.as_str()))
+        })
+    }
+}
+
+/// The differences between two sets of collections, such as those of two servers.
+///
+/// # Fields
+///
+/// - `only_in_a`: IDs of the collections present only in the first set.
+/// - `only_in_b`: IDs of the collections present only in the second set.
+/// - `differing`: IDs present in both sets whose `can_read`, `can_write`, or `media_types`
+///   differ.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CollectionComparison {
+    pub only_in_a: Vec<String>,
+    pub only_in_b: Vec<String>,
+    pub differing: Vec<String>,
+}
+
+impl CollectionComparison {
+    /// Returns `true` if both sets hold the same collections with the same access and media
+    /// types.
+    #[must_use]
+    pub fn is_equivalent(&self) -> bool {
+        self.only_in_a.is_empty() && self.only_in_b.is_empty() && self.differing.is_empty()
+    }
+}
+
+/// Compares two sets of collections by ID, for example to validate a server migration.
+///
+/// Collections are matched by `id`. Names and titles are not compared, and media types are
+/// compared after [`Collection::normalize_media_type`].
+///
+/// # Returns
+///
+/// Returns a `CollectionComparison` whose lists keep the order of the input sets.
+///
+/// # Examples
+///
+/// ```
+/// let comparison = compare_collections(&prod.collections, &staging.collections);
+/// assert!(comparison.is_equivalent());
+/// ```
+#[must_use]
+pub fn compare_collections(a: &[Collection], b: &[Collection]) -> CollectionComparison {
+    let mut comparison = CollectionComparison::default();
+    for collection in a {
+        match b.iter().find(|other| other.id == collection.id) {
+            None => comparison.only_in_a.push(collection.id.clone()),
+            Some(other)
+                if collection.can_read != other.can_read
+                    || collection.can_write != other.can_write
+                    || !same_media_types(collection, other) =>
+            {
+                comparison.differing.push(collection.id.clone());
+            }
+            Some(_) => {}
+        }
+    }
+    comparison.only_in_b = b
+        .iter()
+        .filter(|collection| !a.iter().any(|other| other.id == collection.id))
+        .map(|collection| collection.id.clone())
+        .collect();
+    comparison
+}
+
+/// Returns `true` if both collections advertise the same media types once normalized.
+fn same_media_types(a: &Collection, b: &Collection) -> bool {
+    a.media_types.len() == b.media_types.len()
+        && a.media_types.iter().zip(&b.media_types).all(|(a, b)| {
+            Collection::normalize_media_type(a) == Collection::normalize_media_type(b)
+        })
+}
+
+/// Deserializes a boolean given either as a JSON boolean or as the string `"true"` or
+/// `"false"`, ignoring case.
+fn deserialize_lenient_bool<'de, D>(deserializer: D) -> std::result::Result<bool, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrString {
+        Bool(bool),
+        String(String),
+    }
+
+    match BoolOrString::deserialize(deserializer)? {
+        BoolOrString::Bool(value) => Ok(value),
+        BoolOrString::String(value) if value.eq_ignore_ascii_case("true") => Ok(true),
+        BoolOrString::String(value) if value.eq_ignore_ascii_case("false") => Ok(false),
+        BoolOrString::String(value) => Err(serde::de::Error::custom(format!(
+            "expected a boolean, got {value:?}"
+        ))),
+    }
+}
+
 /// A container for multiple `Collection` objects.
 ///
 /// This struct is typically used to group multiple collections returned from a TAXII server.
@@ -200,7 +767,225 @@ pub struct Collection {
 /// # Fields
 ///
 /// - `collections`: A vector of `Collection` structs.
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Collections {
     pub collections: Vec<Collection>,
 }
+
+/// An overview of a TAXII server: its discovery information and the collections of one of
+/// its API roots.
+///
+/// # Fields
+///
+/// - `discovery`: The discovery information of the server.
+/// - `collections`: The collections of the API root, with all their details.
+#[derive(Debug)]
+pub struct ServerOverview {
+    pub discovery: Discovery,
+    pub collections: Vec<Collection>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    struct StubTaxiiClient {
+        requested: RefCell<Vec<String>>,
+    }
+
+    impl TaxiiClient for StubTaxiiClient {
+        fn new(_username: &str, _api_key: &str) -> Self {
+            Self {
+                requested: RefCell::new(Vec::new()),
+            }
+        }
+
+        fn request_method(
+            &self,
+            _method: &str,
+            url: &str,
+            _body: Option<&[u8]>,
+        ) -> Result<Response> {
+            self.requested.borrow_mut().push(url.to_string());
+            let body = match url.split_once("?next=") {
+                Some((_, "p2")) => r#"{"more":true,"next":"p2","objects":[{"id":"indicator--2"}]}"#,
+                Some(_) => r#"{"objects":[{"id":"indicator--3"}]}"#,
+                None if url.contains("paged") => {
+                    r#"{"more":true,"next":"p2","objects":[{"id":"indicator--1","labels":["a"]}]}"#
+                }
+                None => {
+                    r#"{"more":false,"objects":[{"id":"indicator--1","type":"indicator","labels":["malicious-activity"],"confidence":85}]}"#
+                }
+            };
+            Ok(Response::new(200, "OK", body).expect("Failed to build response"))
+        }
+
+        fn get_discovery(&self) -> Result<Discovery> {
+            Ok(Discovery {
+                api_roots: vec!["/feed/".to_string()],
+                contact: "contact@example.com".to_string(),
+                default: "/feed/".to_string(),
+                description: "Mock server".to_string(),
+                title: "Mock".to_string(),
+            })
+        }
+
+        fn get_collections(&self, root: Option<&str>) -> Result<Vec<String>> {
+            Ok(vec![format!("{}-collection", root.unwrap_or("api"))])
+        }
+    }
+
+    #[test]
+    fn get_default_objects_test() {
+        let mock = StubTaxiiClient::new("user", "key");
+        let agent: &dyn TaxiiClient = &mock;
+        let envelope = agent
+            .get_default_objects(false)
+            .expect("Failed to get default objects");
+        assert_eq!(envelope.objects[0]["id"], "indicator--1");
+        assert_eq!(envelope.objects[0]["labels"][0], "malicious-activity");
+        assert_eq!(envelope.objects[0]["confidence"], 85);
+        assert_eq!(
+            *mock.requested.borrow(),
+            ["feed/collections/feed-collection/objects/"]
+        );
+        assert!(agent.get_default_objects(true).is_err());
+    }
+
+    #[test]
+    fn status_failed_ids_test() {
+        let status: Status = serde_json::from_str(
+            r#"{"id":"status-1","status":"complete","total_count":3,"success_count":1,
+            "successes":[{"id":"indicator--a","version":"2024-01-01T00:00:00Z"}],
+            "failure_count":2,"failures":[
+                {"id":"indicator--b","version":"2024-01-01T00:00:00Z","message":"Invalid pattern"},
+                {"id":"indicator--c","version":"2024-01-01T00:00:00Z","message":"Duplicate"}
+            ],"pending_count":0}"#,
+        )
+        .expect("Failed to parse status");
+        assert_eq!(status.failed_ids(), ["indicator--b", "indicator--c"]);
+        let failures = status.failures.expect("No failures parsed");
+        assert_eq!(failures[0].message.as_deref(), Some("Invalid pattern"));
+        assert!(status.pendings.is_none());
+    }
+
+    fn collection(id: &str, can_write: bool) -> Collection {
+        Collection {
+            can_read: true,
+            can_write,
+            id: id.to_string(),
+            media_types: ["application/stix+json;version=2.1".to_string()],
+            name: id.to_string(),
+            title: id.to_string(),
+        }
+    }
+
+    #[test]
+    fn compare_collections_test() {
+        let prod = [
+            collection("shared", false),
+            collection("changed", false),
+            collection("old", false),
+        ];
+        let staging = [
+            collection("new", false),
+            collection("changed", true),
+            collection("shared", false),
+        ];
+        let comparison = compare_collections(&prod, &staging);
+        assert_eq!(comparison.only_in_a, ["old"]);
+        assert_eq!(comparison.only_in_b, ["new"]);
+        assert_eq!(comparison.differing, ["changed"]);
+        assert!(!comparison.is_equivalent());
+        assert!(compare_collections(&prod, &prod).is_equivalent());
+    }
+
+    #[test]
+    fn media_type_test() {
+        let stix = collection("c1", false);
+        assert!(stix.supports_media_type("application/stix+json"));
+        assert!(stix.supports_media_type("application/stix+json;version=2.1"));
+        assert!(stix.supports_media_type("Application/STIX+json; charset=utf-8"));
+        assert!(!stix.supports_media_type("application/stix+json;version=2.0"));
+        assert!(!stix.supports_media_type("application/taxii+json"));
+        let mut charset = collection("c1", false);
+        charset.media_types = ["application/stix+json; charset=UTF-8; version=2.1".to_string()];
+        assert!(compare_collections(&[stix], &[charset]).is_equivalent());
+    }
+
+    #[test]
+    fn serialize_round_trip_test() {
+        let body = r#"{"collections":[{"can_read":true,"can_write":false,"id":"c1","media_types":["application/stix+json;version=2.1"],"name":"mock","title":"Mock collection"}]}"#;
+        let collections: Collections = serde_json::from_str(body).expect("Failed to parse");
+        let json = serde_json::to_string(&collections).expect("Failed to serialize");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&json).ok(),
+            serde_json::from_str::<serde_json::Value>(body).ok()
+        );
+        let envelope: Envelope =
+            serde_json::from_str(r#"{"objects":[{"id":"a"}]}"#).expect("Failed to parse");
+        let json = serde_json::to_string(&envelope).expect("Failed to serialize");
+        assert_eq!(json, r#"{"objects":[{"id":"a"}]}"#);
+    }
+
+    #[test]
+    fn lenient_bool_test() {
+        let body = |can_read: &str| {
+            format!(
+                r#"{{"can_read":{can_read},"can_write":"False","id":"c1","media_types":["application/stix+json;version=2.1"],"name":"mock","title":"Mock collection"}}"#
+            )
+        };
+        let collection: Collection =
+            serde_json::from_str(&body(r#""true""#)).expect("Failed to parse");
+        assert!(collection.can_read);
+        assert!(!collection.can_write);
+        let collection: Collection = serde_json::from_str(&body("true")).expect("Failed to parse");
+        assert!(collection.can_read);
+        assert!(serde_json::from_str::<Collection>(&body(r#""yes""#)).is_err());
+    }
+
+    #[test]
+    fn generic_envelope_test() {
+        #[derive(Deserialize)]
+        struct Malware {
+            name: String,
+        }
+
+        let envelope: Envelope<Malware> =
+            serde_json::from_str(r#"{"more":true,"next":"p2","objects":{"name":"Emotet"}}"#)
+                .expect("Failed to parse");
+        assert!(envelope.more());
+        assert_eq!(envelope.next(), Some("p2"));
+        assert_eq!(envelope.objects()[0].name, "Emotet");
+        let empty: Envelope = serde_json::from_str(r#"{"more":false}"#).expect("Failed to parse");
+        assert!(empty.objects.is_empty());
+    }
+
+    #[test]
+    fn get_objects_test() {
+        let agent = StubTaxiiClient::new("user", "key");
+        let envelope: Envelope<serde_json::Value> = agent
+            .get_objects("feed", "paged")
+            .expect("Failed to get objects");
+        let ids: Vec<&str> = envelope
+            .objects()
+            .iter()
+            .filter_map(|object| object["id"].as_str())
+            .collect();
+        assert_eq!(ids, ["indicator--1", "indicator--2"]);
+        assert!(!envelope.more());
+        assert_eq!(
+            *agent.requested.borrow(),
+            [
+                "feed/collections/paged/objects/",
+                "feed/collections/paged/objects/?next=p2"
+            ]
+        );
+        let dyn_agent: &dyn TaxiiClient = &agent;
+        let raw = dyn_agent
+            .get_objects_json("feed", "paged")
+            .expect("Failed to get objects");
+        assert_eq!(raw.objects()[1]["id"], "indicator--2");
+    }
+}
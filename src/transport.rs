@@ -0,0 +1,151 @@
+use crate::{
+    error::HttpError,
+    Result, TaxiiError,
+    TaxiiError::{
+        TaxiiAuthorizationError, TaxiiBadRequest, TaxiiConnectionError, TaxiiGenericError,
+        TaxiiNotFound, TaxiiRateLimited, TaxiiServerError, Timeout,
+    },
+};
+use std::time::Duration;
+use ureq::Response;
+
+/// The HTTP backend used to send requests to a TAXII server.
+///
+/// Requests are sent with `ureq` by default. Enabling the `reqwest-backend` feature sends them
+/// through `reqwest::blocking` instead. Either way responses are returned as `ureq::Response`,
+/// so the public API does not change with the backend.
+pub struct Transport {
+    #[cfg(not(feature = "reqwest-backend"))]
+    agent: ureq::Agent,
+    #[cfg(feature = "reqwest-backend")]
+    client: reqwest::blocking::Client,
+}
+
+impl Transport {
+    /// Creates a transport for the backend selected at compile time.
+    #[cfg(not(feature = "reqwest-backend"))]
+    pub fn new() -> Self {
+        Self {
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    /// Creates a transport for the backend selected at compile time.
+    #[cfg(feature = "reqwest-backend")]
+    pub fn new() -> Self {
+        Self {
+            client: reqwest::blocking::Client::new(),
+        }
+    }
+
+    /// Sends a `method` request to `url` with the given headers, optional body, and timeout.
+    ///
+    /// Non-successful status codes are mapped to the matching `TaxiiError` variant.
+    #[cfg(not(feature = "reqwest-backend"))]
+    pub fn send(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+        timeout: Duration,
+    ) -> Result<Response> {
+        let request = headers
+            .iter()
+            .fold(self.agent.request(method, url), |req, (key, value)| {
+                req.set(key, value)
+            })
+            .timeout(timeout);
+        let result = match body {
+            Some(bytes) => request.send_bytes(bytes),
+            None => request.call(),
+        };
+        match result {
+            Ok(response) => Ok(response),
+            Err(ureq::Error::Status(code, response)) => Err(status_error(code, response)),
+            Err(ureq::Error::Transport(transport)) => {
+                let timed_out = std::error::Error::source(&transport)
+                    .and_then(|source| source.downcast_ref::<std::io::Error>())
+                    .is_some_and(|e| {
+                        matches!(
+                            e.kind(),
+                            std::io::ErrorKind::TimedOut | std::io::ErrorKind::WouldBlock
+                        )
+                    });
+                Err(connection_error(timed_out))
+            }
+        }
+    }
+
+    /// Sends a `method` request to `url` with the given headers, optional body, and timeout.
+    ///
+    /// Non-successful status codes are mapped to the matching `TaxiiError` variant.
+    #[cfg(feature = "reqwest-backend")]
+    pub fn send(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        body: Option<&[u8]>,
+        timeout: Duration,
+    ) -> Result<Response> {
+        let method = reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|e| TaxiiConnectionError(e.to_string()))?;
+        let request = headers
+            .iter()
+            .fold(self.client.request(method, url), |req, (key, value)| {
+                req.header(*key, *value)
+            })
+            .timeout(timeout);
+        let request = match body {
+            Some(bytes) => request.body(bytes.to_vec()),
+            None => request,
+        };
+        let response = request
+            .send()
+            .map_err(|e| connection_error(e.is_timeout()))?;
+        let builder = response.headers().iter().fold(
+            http::Response::builder()
+                .status(response.status())
+                .version(response.version()),
+            |builder, (name, value)| builder.header(name, value),
+        );
+        let body = response
+            .bytes()
+            .map_err(|e| TaxiiConnectionError(e.to_string()))?;
+        let response: Response = builder
+            .body(body.to_vec())
+            .map_err(|e| TaxiiConnectionError(e.to_string()))?
+            .into();
+        match response.status() {
+            code if code >= 400 => Err(status_error(code, response)),
+            _ => Ok(response),
+        }
+    }
+}
+
+/// Builds the error for a request that failed to execute, distinguishing timeouts.
+fn connection_error(timed_out: bool) -> Box<TaxiiError> {
+    if timed_out {
+        Box::new(Timeout("Request timed out".to_string()))
+    } else {
+        Box::new(TaxiiConnectionError(
+            "Request failed to execute".to_string(),
+        ))
+    }
+}
+
+/// Maps a non-successful HTTP status code to a `TaxiiError`.
+///
+/// The response body is read into the error, so the server's TAXII error message survives.
+pub fn status_error(code: u16, response: Response) -> Box<TaxiiError> {
+    let response = HttpError::from_response(response);
+    match code {
+        400 => Box::new(TaxiiBadRequest(response)),
+        401 => Box::new(TaxiiAuthorizationError(response)),
+        404 => Box::new(TaxiiNotFound(response)),
+        429 => Box::new(TaxiiRateLimited(response)),
+        500..=599 => Box::new(TaxiiServerError(response)),
+        _ => Box::new(TaxiiGenericError(response)),
+    }
+}